@@ -1,11 +1,15 @@
-use divan::Bencher;
+use divan::{AllocProfiler, Bencher};
 use rand::Rng;
 use rand::prelude::IndexedRandom;
-use redirector::config::AppConfig;
-use redirector::{get_bang, resolve, update_bangs};
+use redirector::bang::Bang;
+use redirector::config::{AppConfig, AppState};
+use redirector::{get_bang, resolve, update_bangs, update_cache};
 use tracing::Level;
 use tracing::error;
 
+#[global_allocator]
+static ALLOC: AllocProfiler = AllocProfiler::system();
+
 fn main() {
     tracing_subscriber::fmt()
         .with_max_level(Level::INFO)
@@ -20,26 +24,62 @@ fn resolve_plain_query(bencher: Bencher) {
     bencher.bench(|| resolve(&config, "just a regular search query"));
 }
 
+#[divan::bench(sample_count = 10_000)]
+fn resolve_long_single_word(bencher: Bencher) {
+    let config = AppConfig::default();
+    let query = "a".repeat(2048);
+    bencher.bench(|| resolve(&config, &query));
+}
+
 #[divan::bench(sample_count = 10_000)]
 fn resolve_query_with_bang(bencher: Bencher) {
     let config = create_config();
     bencher.bench(|| resolve(&config, "!gh just a regular search query"));
 }
 
+#[divan::bench(sample_count = 10_000)]
+fn resolve_cached_bang(bencher: Bencher) {
+    let config = AppConfig::default();
+    update_cache(synthetic_bangs(), &config);
+    bencher.bench(|| resolve(&config, "!bang1234 just a regular search query"));
+}
+
+#[divan::bench(sample_count = 10_000)]
+fn resolve_with_cloned_config(bencher: Bencher) {
+    let app_state = configured_app_state();
+    bencher.bench(|| {
+        resolve(
+            &app_state.get_config(),
+            "!bang1234 just a regular search query",
+        )
+    });
+}
+
+#[divan::bench(sample_count = 10_000)]
+fn resolve_with_borrowed_config(bencher: Bencher) {
+    let app_state = configured_app_state();
+    bencher.bench(|| {
+        resolve(
+            &app_state.read_config(),
+            "!bang1234 just a regular search query",
+        )
+    });
+}
+
 #[divan::bench(sample_count = 10_000)]
 fn resolve_random_generated_query(bencher: Bencher) {
     let config = create_config();
     bencher
-        .with_inputs(|| generate_random_query())
+        .with_inputs(generate_random_query)
         .bench_values(|query| resolve(&config, &query));
 }
 
 #[divan::bench(sample_count = 10_000)]
 fn get_bang_random(bencher: Bencher) {
     bencher
-        .with_inputs(|| generate_random_query())
+        .with_inputs(generate_random_query)
         .bench_values(|query| {
-            let _ = get_bang(&*query);
+            let _ = get_bang(&query);
         });
 }
 
@@ -54,6 +94,29 @@ fn create_config() -> AppConfig {
     })
 }
 
+/// App state with a handful of configured bangs, as the server holds it per request.
+fn configured_app_state() -> AppState {
+    let bangs = synthetic_bangs();
+    let config = AppConfig {
+        bangs: Some(bangs[..50].to_vec()),
+        ..AppConfig::default()
+    };
+    update_cache(bangs, &config);
+    AppState::new(config)
+}
+
+/// A bang list about the size of DuckDuckGo's, so benchmarks don't depend on the network.
+fn synthetic_bangs() -> Vec<Bang> {
+    (0..15_000)
+        .map(|i| {
+            Bang::new(
+                format!("bang{i}"),
+                format!("https://example{i}.com/search?q={{{{{{s}}}}}}"),
+            )
+        })
+        .collect()
+}
+
 fn generate_random_query() -> String {
     let bang_commands = [
         "!g",
@@ -92,13 +92,11 @@ fn benchmark_resolve(c: &mut Criterion) {
 }
 
 fn benchmark_get_bang(c: &mut Criterion) {
-    let config = create_config();
-
     c.bench_function("get bang", |b| {
         b.iter_batched(
             generate_random_query,
             |query| {
-                let _ = get_bang(&*query);
+                let _ = get_bang(&query);
             },
             BatchSize::SmallInput,
         )
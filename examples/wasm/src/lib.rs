@@ -0,0 +1,32 @@
+//! Resolve queries in the browser against a bang list supplied from JavaScript.
+//!
+//! Build it for WebAssembly from this directory and generate the bindings with `wasm-bindgen`:
+//!
+//! ```sh
+//! cargo build --release --target wasm32-unknown-unknown
+//! wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/wasm_resolve.wasm
+//! ```
+//!
+//! ```js
+//! import init, { resolve } from "./pkg/wasm_resolve.js";
+//!
+//! await init();
+//! const bangs = JSON.stringify([{ t: "g", u: "https://www.google.com/search?q={{{s}}}" }]);
+//! location.href = resolve(bangs, "!g rust");
+//! ```
+
+use redirector::bang::BangSource;
+use redirector::config::AppConfig;
+use redirector::{BangMap, resolve_with};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// Resolve `query` against `bangs`, a bang list in the format of DuckDuckGo's `bang.js`.
+///
+/// # Errors
+/// If `bangs` is not a bang list.
+#[wasm_bindgen]
+pub fn resolve(bangs: &str, query: &str) -> Result<String, String> {
+    let BangSource(bangs) = serde_json::from_str(bangs).map_err(|e| e.to_string())?;
+    let bangs: BangMap = bangs.into_iter().map(|bang| (bang.key(), bang)).collect();
+    Ok(resolve_with(&bangs, &AppConfig::default(), query).url)
+}
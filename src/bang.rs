@@ -1,32 +1,317 @@
-use serde::{Deserialize, Serialize};
+use serde::de::{self, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::fmt::Display;
+use url::Url;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Placeholders a URL template may contain for the search term.
+pub const PLACEHOLDERS: [&str; 3] = ["{{{s}}}", "{}", "%s"];
+
+/// Trigger of the bang used for bangs that match no other trigger.
+pub const WILDCARD_TRIGGER: &str = "*";
+
+/// Placeholder in the URL template of the [`WILDCARD_TRIGGER`] bang for the trigger typed.
+pub const TRIGGER_PLACEHOLDER: &str = "{t}";
+
+/// Longest trigger accepted by [`Bang::validate`], in bytes.
+pub const MAX_TRIGGER_LEN: usize = 64;
+
+/// Longest URL template accepted by [`Bang::validate`], in bytes.
+pub const MAX_URL_TEMPLATE_LEN: usize = 2048;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct Bang {
     /// The category of the bang command (e.g., "Tech", "Entertainment").
-    #[serde(alias = "category", rename = "c")]
+    #[serde(alias = "c", skip_serializing_if = "Option::is_none")]
     pub category: Option<Category>,
     /// The domain associated with the bang command (e.g., "www.example.com").
-    #[serde(alias = "domain", rename = "d")]
+    #[serde(alias = "d", skip_serializing_if = "Option::is_none")]
     pub domain: Option<String>,
     /// The relevance score of the bang command.
-    #[serde(alias = "relevance", rename = "r")]
+    #[serde(alias = "r", skip_serializing_if = "Option::is_none")]
     pub relevance: Option<u64>,
     /// The short name or abbreviation of the bang command.
-    #[serde(alias = "short_name", rename = "s")]
+    #[serde(alias = "s", skip_serializing_if = "Option::is_none")]
     pub short_name: Option<String>,
     /// The subcategory of the bang command, if applicable.
-    #[serde(alias = "subcategory", rename = "sc")]
+    #[serde(alias = "sc", skip_serializing_if = "Option::is_none")]
     pub subcategory: Option<String>,
     /// The trigger text for the bang command (e.g., "g" for Google).
-    #[serde(alias = "trigger", rename = "t")]
+    #[serde(alias = "t")]
     pub trigger: String,
     /// The URL template where the search term is inserted.
-    #[serde(alias = "url_template", rename = "u")]
+    #[serde(alias = "u")]
     pub url_template: String,
+    /// Whether a template without a placeholder is used verbatim instead of having the term
+    /// appended.
+    /// Overrides the global `default_template_mode` for this bang.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ignore_term: Option<bool>,
+    /// Whether `%2F` in the encoded search term is turned back into `/`.
+    /// Overrides the global `decode_slashes` for this bang.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decode_slashes: Option<bool>,
+    /// Whether spaces in the search term are encoded as `+` instead of `%20`.
+    /// Overrides the global `space_as_plus` for this bang.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub space_as_plus: Option<bool>,
+    /// Whether the search term is percent-encoded twice, for templates that nest the search URL
+    /// in a query parameter of another URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub double_encode: Option<bool>,
+    /// The HTTP method the search is sent with, `get` unless set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<BangMethod>,
+    /// Form fields sent by a `post` bang, e.g. `query={{{s}}}&type=all`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_template: Option<String>,
+    /// Whether to show a page with a link to the destination instead of redirecting instantly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirm: Option<bool>,
+    /// Whether redirects through this bang are kept out of the logs and usage stats.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_log: Option<bool>,
+    /// The status redirects through this bang are sent with.
+    /// Overrides the global `redirect_status` for this bang.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<RedirectStatus>,
+    /// A note on what the bang is for, shown with the configured bangs on the bangs page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Where the bang was loaded from. Only tracked in the cache, never serialized.
+    #[serde(skip)]
+    pub source: BangSourceKind,
+}
+
+/// How a bang sends the search to its service.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BangMethod {
+    /// Redirect to the filled URL template.
+    #[default]
+    Get,
+    /// Submit the filled `body_template` as a form to the URL template.
+    Post,
+}
+
+/// The HTTP status of a redirect, written as its number, e.g. `301`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(try_from = "u16", into = "u16")]
+pub enum RedirectStatus {
+    /// `301 Moved Permanently`, cached by browsers, for destinations that never change.
+    MovedPermanently,
+    /// `302 Found`.
+    Found,
+    /// `303 See Other`.
+    #[default]
+    SeeOther,
+    /// `307 Temporary Redirect`.
+    TemporaryRedirect,
+    /// `308 Permanent Redirect`.
+    PermanentRedirect,
+}
+
+impl RedirectStatus {
+    /// The status code.
+    #[must_use]
+    pub const fn code(self) -> u16 {
+        match self {
+            Self::MovedPermanently => 301,
+            Self::Found => 302,
+            Self::SeeOther => 303,
+            Self::TemporaryRedirect => 307,
+            Self::PermanentRedirect => 308,
+        }
+    }
+}
+
+impl TryFrom<u16> for RedirectStatus {
+    type Error = String;
+
+    fn try_from(code: u16) -> Result<Self, Self::Error> {
+        match code {
+            301 => Ok(Self::MovedPermanently),
+            302 => Ok(Self::Found),
+            303 => Ok(Self::SeeOther),
+            307 => Ok(Self::TemporaryRedirect),
+            308 => Ok(Self::PermanentRedirect),
+            _ => Err(format!(
+                "{code} is not a redirect status, expected 301, 302, 303, 307 or 308"
+            )),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl From<RedirectStatus> for u16 {
+    fn from(status: RedirectStatus) -> Self {
+        status.code()
+    }
+}
+
+/// Where the bang a query resolved with came from.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BangSourceKind {
+    /// A bang from the configuration file, including ones added through `/add_bang`.
+    Config,
+    /// A bang from the remote bang list.
+    #[default]
+    Remote,
+    /// One of the few common bangs built into redirector, see `builtin_bangs`.
+    Builtin,
+    /// No bang was used, the query went to a search engine.
+    Default,
+}
+
+/// The bangs published by a bang source.
+///
+/// Accepts both a bare array of bangs, as served by DuckDuckGo, and exports that wrap the
+/// array in a `{ "bangs": [...] }` envelope.
+#[derive(Debug, Clone, Default)]
+pub struct BangSource(pub Vec<Bang>);
+
+impl<'de> Deserialize<'de> for BangSource {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BangSourceVisitor;
+
+        impl<'de> Visitor<'de> for BangSourceVisitor {
+            type Value = BangSource;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("an array of bangs or an object with a `bangs` array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut bangs = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(bang) = seq.next_element()? {
+                    bangs.push(bang);
+                }
+                Ok(BangSource(bangs))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut bangs = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    if key == "bangs" {
+                        if bangs.is_some() {
+                            return Err(de::Error::duplicate_field("bangs"));
+                        }
+                        bangs = Some(map.next_value()?);
+                    } else {
+                        map.next_value::<IgnoredAny>()?;
+                    }
+                }
+                bangs
+                    .map(BangSource)
+                    .ok_or_else(|| de::Error::missing_field("bangs"))
+            }
+        }
+
+        deserializer.deserialize_any(BangSourceVisitor)
+    }
+}
+
+impl Bang {
+    /// Create a bang with only a trigger and URL template.
+    #[must_use]
+    pub fn new(trigger: impl Into<String>, url_template: impl Into<String>) -> Self {
+        Self {
+            trigger: trigger.into(),
+            url_template: url_template.into(),
+            ..Self::default()
+        }
+    }
+
+    /// The key of this bang in the cache. Triggers are matched case-insensitively, so this is
+    /// the trigger in lowercase.
+    #[must_use]
+    pub fn key(&self) -> String {
+        self.trigger.to_ascii_lowercase()
+    }
+
+    /// A copy of this bang marked as coming from the configuration.
+    #[must_use]
+    pub fn configured(&self) -> Self {
+        Self {
+            source: BangSourceKind::Config,
+            ..self.clone()
+        }
+    }
+
+    /// Check that the bang has a trigger and a URL template that can take a search term.
+    ///
+    /// # Errors
+    /// If the trigger is empty, the trigger or template is longer than [`MAX_TRIGGER_LEN`] or
+    /// [`MAX_URL_TEMPLATE_LEN`], the template is not an http(s) URL, or the template has no
+    /// placeholder and does not end in a point the search term can be appended to.
+    pub fn validate(&self) -> Result<(), InvalidBang> {
+        if self.trigger.trim().is_empty() {
+            return Err(InvalidBang::EmptyTrigger);
+        }
+        if self.trigger.len() > MAX_TRIGGER_LEN {
+            return Err(InvalidBang::TooLong("trigger", MAX_TRIGGER_LEN));
+        }
+        if self.url_template.len() > MAX_URL_TEMPLATE_LEN {
+            return Err(InvalidBang::TooLong("url_template", MAX_URL_TEMPLATE_LEN));
+        }
+        // The wildcard bang's template only becomes a URL once the trigger typed is filled in.
+        let url = if self.trigger == WILDCARD_TRIGGER {
+            Url::parse(&self.url_template.replace(TRIGGER_PLACEHOLDER, "t"))
+        } else {
+            Url::parse(&self.url_template)
+        };
+        let url = url.map_err(|_| InvalidBang::InvalidUrl)?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(InvalidBang::UnsupportedScheme(url.scheme().to_string()));
+        }
+        let has_placeholder = PLACEHOLDERS
+            .iter()
+            .any(|placeholder| self.url_template.contains(placeholder));
+        let is_append_target = self.url_template.ends_with(['=', '/', '+']);
+        // A POST bang may carry the search term in its body instead.
+        let has_body_placeholder = self.method == Some(BangMethod::Post)
+            && self
+                .body_template
+                .as_ref()
+                .is_some_and(|body| body.contains("{{{s}}}"));
+        if !has_placeholder && !is_append_target && !has_body_placeholder {
+            return Err(InvalidBang::NoPlaceholder);
+        }
+        Ok(())
+    }
+}
+
+/// Why a bang was rejected by [`Bang::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidBang {
+    EmptyTrigger,
+    /// The named field is longer than the given number of bytes.
+    TooLong(&'static str, usize),
+    InvalidUrl,
+    UnsupportedScheme(String),
+    NoPlaceholder,
+}
+
+impl Display for InvalidBang {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyTrigger => write!(f, "trigger must not be empty"),
+            Self::TooLong(field, max) => write!(f, "{field} must be at most {max} bytes long"),
+            Self::InvalidUrl => write!(f, "url_template is not a valid absolute URL"),
+            Self::UnsupportedScheme(scheme) => {
+                write!(f, "url_template must use http or https, not '{scheme}'")
+            }
+            Self::NoPlaceholder => write!(
+                f,
+                "url_template must contain a placeholder ({}) or end with '=', '/' or '+' so the search term can be appended",
+                PLACEHOLDERS.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InvalidBang {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "PascalCase")]
 pub enum Category {
     Entertainment,
@@ -40,6 +325,20 @@ pub enum Category {
     Translation,
 }
 
+impl Category {
+    /// All categories, in display order.
+    pub const ALL: [Self; 8] = [
+        Self::Entertainment,
+        Self::Multimedia,
+        Self::News,
+        Self::OnlineServices,
+        Self::Research,
+        Self::Shopping,
+        Self::Tech,
+        Self::Translation,
+    ];
+}
+
 impl Display for Category {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -54,3 +353,100 @@ impl Display for Category {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_bang() {
+        let valid = Bang {
+            trigger: "ex".to_string(),
+            url_template: "https://example.com/search?q={{{s}}}".to_string(),
+            ..Bang::default()
+        };
+        assert_eq!(valid.validate(), Ok(()));
+
+        let append = Bang {
+            url_template: "https://example.com/search?q=".to_string(),
+            ..valid.clone()
+        };
+        assert_eq!(append.validate(), Ok(()));
+
+        let schemeless = Bang {
+            url_template: "example.com/search?q={{{s}}}".to_string(),
+            ..valid.clone()
+        };
+        assert_eq!(schemeless.validate(), Err(InvalidBang::InvalidUrl));
+
+        let unsupported = Bang {
+            url_template: "ftp://example.com/{{{s}}}".to_string(),
+            ..valid.clone()
+        };
+        assert_eq!(
+            unsupported.validate(),
+            Err(InvalidBang::UnsupportedScheme("ftp".to_string()))
+        );
+
+        let placeholderless = Bang {
+            url_template: "https://example.com/search".to_string(),
+            ..valid.clone()
+        };
+        assert_eq!(placeholderless.validate(), Err(InvalidBang::NoPlaceholder));
+        // A POST bang can take the search term in its body instead.
+        let post = Bang {
+            method: Some(BangMethod::Post),
+            body_template: Some("query={{{s}}}".to_string()),
+            ..placeholderless
+        };
+        assert_eq!(post.validate(), Ok(()));
+
+        let empty_trigger = Bang {
+            trigger: " ".to_string(),
+            ..valid.clone()
+        };
+        assert_eq!(empty_trigger.validate(), Err(InvalidBang::EmptyTrigger));
+
+        let long_trigger = Bang {
+            trigger: "x".repeat(MAX_TRIGGER_LEN + 1),
+            ..valid.clone()
+        };
+        assert_eq!(
+            long_trigger.validate(),
+            Err(InvalidBang::TooLong("trigger", MAX_TRIGGER_LEN))
+        );
+        let long_template = Bang {
+            url_template: format!(
+                "https://example.com/?q={{{{{{s}}}}}}&pad={}",
+                "x".repeat(MAX_URL_TEMPLATE_LEN)
+            ),
+            ..valid
+        };
+        assert_eq!(
+            long_template.validate(),
+            Err(InvalidBang::TooLong("url_template", MAX_URL_TEMPLATE_LEN))
+        );
+    }
+
+    #[test]
+    fn test_bang_source_forms() {
+        let bare = r#"[{"t": "g", "u": "https://www.google.com/search?q={{{s}}}"}]"#;
+        let BangSource(bangs) = serde_json::from_str(bare).unwrap();
+        assert_eq!(bangs.len(), 1);
+        assert_eq!(bangs[0].trigger, "g");
+
+        let enveloped = r#"{
+            "version": 2,
+            "bangs": [
+                {"trigger": "g", "url_template": "https://www.google.com/search?q={{{s}}}", "category": "Online Services"},
+                {"t": "w", "u": "https://en.wikipedia.org/wiki/{{{s}}}", "subcategory": "Reference"}
+            ]
+        }"#;
+        let BangSource(bangs) = serde_json::from_str(enveloped).unwrap();
+        assert_eq!(bangs.len(), 2);
+        assert_eq!(bangs[0].trigger, "g");
+        assert_eq!(bangs[1].subcategory.as_deref(), Some("Reference"));
+
+        assert!(serde_json::from_str::<BangSource>(r#"{"items": []}"#).is_err());
+    }
+}
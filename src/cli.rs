@@ -1,6 +1,7 @@
 use clap::{Parser, Subcommand};
 use clap_complete::Shell;
 use std::net::IpAddr;
+use std::path::PathBuf;
 
 /// Main CLI configuration.
 #[derive(Parser, Debug, Clone)]
@@ -9,9 +10,9 @@ pub struct Cli {
     #[clap(subcommand)]
     pub command: Option<SubCommand>,
 
-    /// URL to fetch bang commands from
+    /// URL to fetch bang commands from, repeat to merge several lists
     #[arg(short, long)]
-    pub bangs_url: Option<String>,
+    pub bangs_url: Vec<String>,
 
     /// Default search engine URL template (use '{}' as placeholder for the query)
     #[arg(short, long)]
@@ -20,6 +21,14 @@ pub struct Cli {
     /// Search suggestions URL template (use '{}' as placeholder for the query)
     #[arg(short, long)]
     pub search_suggestions: Option<String>,
+
+    /// Directory to cache the bang list in, the system temp directory by default
+    #[arg(long, value_name = "DIR", global = true)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Refuse to start when the configuration has problems instead of warning about them
+    #[arg(long, global = true)]
+    pub strict_config: bool,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -33,16 +42,122 @@ pub enum SubCommand {
         /// IP to serve the application on
         #[arg(short, long)]
         ip: Option<IpAddr>,
+
+        /// Unix domain socket to serve the application on instead of a TCP port
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["port", "ip"])]
+        unix_socket: Option<PathBuf>,
     },
     #[command(about = "Resolve a search query", display_order = 2)]
     Resolve {
-        /// The search query to resolve
-        #[arg(required = true)]
-        query: String,
+        /// The search query to resolve, multiple words are joined with spaces. `-` reads queries
+        /// from stdin like `--stdin`
+        #[arg(
+            required_unless_present = "stdin",
+            num_args = 1..,
+            trailing_var_arg = true,
+            allow_hyphen_values = true
+        )]
+        query: Vec<String>,
+
+        /// Read one query per line from stdin and print `query<TAB>url` for each
+        #[arg(long, conflicts_with = "explain")]
+        stdin: bool,
+
+        /// Use only the bang list cached on disk and the configured bangs, without network access
+        #[arg(long)]
+        offline: bool,
+
+        /// Explain the decision: the matched bang, its template and the encoded search term
+        #[arg(long)]
+        explain: bool,
+
+        /// Print the explanation as JSON
+        #[arg(long, requires = "explain")]
+        json: bool,
+    },
+    #[command(
+        about = "Report configured bangs whose services are unreachable",
+        display_order = 3
+    )]
+    Prune {
+        /// Only report dead bangs, the default
+        #[arg(long)]
+        check: bool,
+
+        /// Remove dead bangs from the configuration file
+        #[arg(long, conflicts_with = "check")]
+        apply: bool,
     },
-    #[command(about = "Generate shell completions", display_order = 3)]
+    #[command(about = "Generate shell completions", display_order = 4)]
     Completions {
         #[clap(value_enum)]
         shell: Shell,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unix_socket_conflicts_with_port() {
+        assert!(
+            Cli::try_parse_from(["redirector", "serve", "--unix-socket", "/tmp/r.sock"]).is_ok()
+        );
+        assert!(
+            Cli::try_parse_from([
+                "redirector",
+                "serve",
+                "--unix-socket",
+                "/tmp/r.sock",
+                "--port",
+                "3000"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_resolve_subcommand_joins_args() {
+        let cli =
+            Cli::try_parse_from(["redirector", "resolve", "!gh", "rust", "-programming"]).unwrap();
+        let Some(SubCommand::Resolve { query, .. }) = cli.command else {
+            panic!("expected the resolve subcommand");
+        };
+        assert_eq!(query.join(" "), "!gh rust -programming");
+
+        let cli = Cli::try_parse_from(["redirector", "resolve", "!gh rust programming"]).unwrap();
+        let Some(SubCommand::Resolve { query, .. }) = cli.command else {
+            panic!("expected the resolve subcommand");
+        };
+        assert_eq!(query.join(" "), "!gh rust programming");
+
+        assert!(Cli::try_parse_from(["redirector", "resolve", "--json", "!gh rust"]).is_err());
+        let cli = Cli::try_parse_from(["redirector", "resolve", "--explain", "--json", "!gh rust"])
+            .unwrap();
+        let Some(SubCommand::Resolve {
+            query,
+            explain,
+            json,
+            ..
+        }) = cli.command
+        else {
+            panic!("expected the resolve subcommand");
+        };
+        assert_eq!(query, ["!gh rust"]);
+        assert!(explain && json);
+
+        let cli = Cli::try_parse_from(["redirector", "resolve", "--stdin"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(SubCommand::Resolve { stdin: true, .. })
+        ));
+        assert!(Cli::try_parse_from(["redirector", "resolve"]).is_err());
+
+        let cli = Cli::try_parse_from(["redirector", "resolve", "--offline", "!gh rust"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(SubCommand::Resolve { offline: true, .. })
+        ));
+    }
+}
@@ -1,27 +1,201 @@
-use crate::bang::Bang;
+use crate::BANG_CACHE;
+use crate::bang::{Bang, RedirectStatus};
+#[cfg(feature = "cli")]
 use crate::cli::{Cli, SubCommand};
+use crate::rate_limit::RateLimiter;
+use crate::stats::BangStats;
+#[cfg(feature = "server")]
 use crate::update_bangs;
-use parking_lot::RwLock;
-use serde::{Deserialize, Serialize};
+use ipnet::IpNet;
+use parking_lot::{RwLock, RwLockReadGuard};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
 use std::env;
-use std::fmt::Write;
+use std::ffi::OsString;
+use std::fmt::Display;
 use std::fs::read_to_string;
-use std::net::IpAddr;
-use std::path::Path;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
 use tracing::{debug, error, info};
 
+/// How a bang template without a `{{{s}}}` placeholder is filled.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateMode {
+    /// Append the encoded search term to the template.
+    #[default]
+    Append,
+    /// Use the template as-is, ignoring the search term.
+    Verbatim,
+}
+
+/// A step tried, in order, when a query resolves to a URL that cannot be redirected to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FallbackStep {
+    /// Search the whole query with `default_search`.
+    DefaultSearch,
+    /// Search the whole query with the search engine built into the binary.
+    Embedded,
+    /// Give up and serve an error page.
+    ErrorPage,
+}
+
+/// The fallback steps used when `resolve_fallback_chain` is not configured.
+pub const DEFAULT_RESOLVE_FALLBACK_CHAIN: [FallbackStep; 3] = [
+    FallbackStep::DefaultSearch,
+    FallbackStep::Embedded,
+    FallbackStep::ErrorPage,
+];
+
+/// How verbosely something is logged, from `off` to `error`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Trace,
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// The `tracing` level to log at, or `None` to not log at all.
+    #[must_use]
+    pub const fn level(self) -> Option<tracing::Level> {
+        match self {
+            Self::Off => None,
+            Self::Trace => Some(tracing::Level::TRACE),
+            Self::Debug => Some(tracing::Level::DEBUG),
+            Self::Info => Some(tracing::Level::INFO),
+            Self::Warn => Some(tracing::Level::WARN),
+            Self::Error => Some(tracing::Level::ERROR),
+        }
+    }
+}
+
+/// What `/` answers with when the request has no `q` parameter.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NoQueryAction {
+    /// Redirect to the list of bangs.
+    #[default]
+    BangsPage,
+    /// Redirect to this URL.
+    Homepage(String),
+    /// Answer with `400 Bad Request`.
+    BadRequest,
+    /// Serve a page with a search box submitting back to `/`.
+    SearchBox,
+}
+
+/// Where search suggestions come from, which decides the URL and how the response is read.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SuggestionProvider {
+    #[default]
+    Brave,
+    Google,
+    DuckDuckGo,
+    /// A URL template with a `{}` placeholder, answering with OpenSearch suggestions.
+    Custom(String),
+}
+
+impl SuggestionProvider {
+    /// The URL template suggestions are fetched from, with a `{}` placeholder for the query.
+    #[must_use]
+    pub fn template(&self) -> &str {
+        match self {
+            Self::Brave => DEFAULT_SEARCH_SUGGESTIONS,
+            Self::Google => {
+                "https://suggestqueries.google.com/complete/search?client=chrome&ie=UTF-8&oe=UTF-8&q={}"
+            }
+            Self::DuckDuckGo => "https://duckduckgo.com/ac/?q={}",
+            Self::Custom(template) => template,
+        }
+    }
+}
+
+const DEFAULT_BANGS_URL: &str = "https://duckduckgo.com/bang.js";
 const DEFAULT_SEARCH: &str = "https://www.qwant.com/?q={}";
 const DEFAULT_SEARCH_SUGGESTIONS: &str = "https://search.brave.com/api/suggest?q={}";
+const DEFAULT_SUGGESTIONS_MAX_BYTES: usize = 64 * 1024;
+const DEFAULT_MAX_BANGS_SHRINK_PERCENT: u8 = 50;
+const DEFAULT_MAX_USER_BANGS: usize = 1000;
+
+/// A value written either as a single item or as a list of them, e.g. `bangs_url`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T> From<OneOrMany<T>> for Vec<T> {
+    fn from(value: OneOrMany<T>) -> Self {
+        match value {
+            OneOrMany::One(item) => vec![item],
+            OneOrMany::Many(items) => items,
+        }
+    }
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    OneOrMany::deserialize(deserializer).map(Vec::from)
+}
+
+fn optional_one_or_many<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    Option::<OneOrMany<String>>::deserialize(deserializer).map(|value| value.map(Vec::from))
+}
 
 /// Configuration read from the file.
 #[derive(Deserialize, Debug, Default)]
 pub struct FileConfig {
     pub port: Option<u16>,
     pub ip: Option<IpAddr>,
-    pub bangs_url: Option<String>,
+    pub listen_addrs: Option<Vec<SocketAddr>>,
+    pub allow_ephemeral_port: Option<bool>,
+    pub addr_file: Option<PathBuf>,
+    pub base_path: Option<String>,
+    pub unix_socket: Option<PathBuf>,
+    pub cache_dir: Option<PathBuf>,
+    pub trusted_proxies: Option<Vec<IpNet>>,
+    pub allowed_origins: Option<Vec<String>>,
+    pub admin_token: Option<String>,
+    pub max_user_bangs: Option<usize>,
+    #[serde(default, deserialize_with = "optional_one_or_many")]
+    pub bangs_url: Option<Vec<String>>,
+    pub builtin_bangs: Option<bool>,
     pub default_search: Option<String>,
+    pub default_search_by_lang: Option<HashMap<String, String>>,
     pub search_suggestions: Option<String>,
+    pub enable_suggestions: Option<bool>,
+    pub suggestion_provider: Option<SuggestionProvider>,
+    pub suggestions_max_bytes: Option<usize>,
+    pub default_template_mode: Option<TemplateMode>,
+    pub max_bangs_shrink_percent: Option<u8>,
+    pub reject_shrunk_bangs: Option<bool>,
+    pub strip_words: Option<Vec<String>>,
+    pub query_param: Option<String>,
+    pub plus_as_space: Option<bool>,
+    pub normalize_whitespace: Option<bool>,
+    pub strip_unknown_bang: Option<bool>,
+    pub no_referrer_redirects: Option<bool>,
+    pub redirect_log_level: Option<LogLevel>,
+    pub no_query_action: Option<NoQueryAction>,
+    pub redirect_status: Option<RedirectStatus>,
+    pub decode_slashes: Option<bool>,
+    pub space_as_plus: Option<bool>,
+    pub resolve_fallback_chain: Option<Vec<FallbackStep>>,
+    pub rate_limit_per_min: Option<u32>,
+    pub rate_limit_all_routes: Option<bool>,
+    pub security_headers: Option<HashMap<String, String>>,
     pub bangs: Option<Vec<Bang>>,
 }
 
@@ -30,7 +204,9 @@ pub struct FileConfig {
 pub struct Config {
     pub port: Option<u16>,
     pub ip: Option<IpAddr>,
-    pub bangs_url: Option<String>,
+    pub unix_socket: Option<PathBuf>,
+    pub cache_dir: Option<PathBuf>,
+    pub bangs_url: Option<Vec<String>>,
     pub default_search: Option<String>,
     pub search_suggestions: Option<String>,
 }
@@ -41,15 +217,95 @@ pub struct Config {
 pub struct AppConfig {
     pub port: u16,
     pub ip: IpAddr,
-    pub bangs_url: String,
+    /// Addresses to serve on, replacing `ip` and `port` when set.
+    pub listen_addrs: Option<Vec<SocketAddr>>,
+    /// Whether port `0`, which lets the system pick any free port, is intended.
+    pub allow_ephemeral_port: bool,
+    /// File the addresses the server listens on are written to, one per line, once bound.
+    pub addr_file: Option<PathBuf>,
+    /// Path every route is served under, e.g. `/search`, or empty to serve at the root.
+    pub base_path: String,
+    /// Unix domain socket to serve on instead of `ip` and `port`.
+    pub unix_socket: Option<PathBuf>,
+    /// Directory the bang list is cached in, the system temp directory when unset.
+    pub cache_dir: Option<PathBuf>,
+    /// Networks of reverse proxies whose forwarding headers are trusted for the client address.
+    pub trusted_proxies: Vec<IpNet>,
+    /// Origins allowed to call the JSON endpoints cross-origin, `*` for any.
+    pub allowed_origins: Vec<String>,
+    /// Token required to call the endpoints that change the configuration, open when unset.
+    pub admin_token: Option<String>,
+    /// Most bangs `add_bang` lets the configuration file hold.
+    pub max_user_bangs: usize,
+    /// Bang lists to fetch, in order. Bangs from later lists override earlier ones.
+    #[serde(deserialize_with = "one_or_many")]
+    pub bangs_url: Vec<String>,
+    /// Whether the few common bangs built into redirector are available. Fetched, cached and
+    /// configured bangs override them, and they are not enough to serve on their own.
+    pub builtin_bangs: bool,
     pub default_search: String,
+    /// `default_search` templates by language tag, chosen from the client's `Accept-Language`.
+    pub default_search_by_lang: HashMap<String, String>,
+    /// The URL template of `suggestion_provider`.
     pub search_suggestions: String,
+    /// Whether `/suggest` fetches suggestions and is advertised in `/opensearch.xml`.
+    pub enable_suggestions: bool,
+    /// Where search suggestions come from. A `search_suggestions` template on its own selects
+    /// [`SuggestionProvider::Custom`].
+    pub suggestion_provider: SuggestionProvider,
+    /// Largest `search_suggestions` response relayed to clients, in bytes.
+    pub suggestions_max_bytes: usize,
+    pub default_template_mode: TemplateMode,
+    /// Percentage by which the bang list may shrink between updates before a warning is logged.
+    pub max_bangs_shrink_percent: u8,
+    /// Whether to keep the previous bang list when an update shrinks it too much.
+    pub reject_shrunk_bangs: bool,
+    /// Words removed from queries before resolving, matched whole and case-insensitively.
+    pub strip_words: Option<Vec<String>>,
+    /// Name of the query parameter the search is read from, `q` is accepted as well.
+    pub query_param: String,
+    /// Whether a `+` in the raw `q` parameter is decoded as a space, as in form encoding.
+    pub plus_as_space: bool,
+    /// Whether the search term is trimmed and runs of whitespace in it collapsed to one space.
+    pub normalize_whitespace: bool,
+    /// Whether a bang that matches no trigger is removed before searching with `default_search`.
+    pub strip_unknown_bang: bool,
+    /// Whether redirects are sent as a page that refreshes to the destination without a
+    /// `Referer`, instead of as a bare redirect.
+    pub no_referrer_redirects: bool,
+    /// Level of the access log line of a redirect, other requests are logged at `info`.
+    pub redirect_log_level: LogLevel,
+    /// What `/` answers with when the request has no `q` parameter.
+    pub no_query_action: NoQueryAction,
+    /// The status redirects to search results are sent with, unless the bang sets its own.
+    pub redirect_status: RedirectStatus,
+    /// Whether `%2F` in the encoded search term is turned back into `/`, unless a bang overrides
+    /// it.
+    pub decode_slashes: bool,
+    /// Whether spaces in the search term are encoded as `+`, as older form handlers expect.
+    pub space_as_plus: bool,
+    /// What to try, in order, when a query resolves to a malformed URL.
+    pub resolve_fallback_chain: Vec<FallbackStep>,
+    /// Requests each client may make per minute to rate-limited routes, `0` for no limit.
+    pub rate_limit_per_min: u32,
+    /// Whether the rate limit applies to every route instead of only `/suggest`.
+    pub rate_limit_all_routes: bool,
+    /// Headers sent with HTML pages in place of the built-in security headers, an empty value
+    /// leaves that header out.
+    pub security_headers: HashMap<String, String>,
     pub bangs: Option<Vec<Bang>>,
 }
 
 #[derive(Clone, Debug)]
 pub struct AppState {
     pub config: Arc<RwLock<AppConfig>>,
+    pub stats: Arc<BangStats>,
+    pub rate_limiter: Arc<RateLimiter>,
+    /// The addresses the server listens on, once bound. Unlike the configured addresses these
+    /// have the port the system picked for port `0`.
+    pub bound_addrs: Arc<RwLock<Vec<SocketAddr>>>,
+    /// The configuration file bangs are added to and reloaded from, [`config_path`] by default.
+    pub config_path: Arc<Path>,
 }
 
 impl AppState {
@@ -57,6 +313,19 @@ impl AppState {
     pub fn new(config: AppConfig) -> Self {
         Self {
             config: Arc::new(RwLock::new(config)),
+            stats: Arc::new(BangStats::default()),
+            rate_limiter: Arc::new(RateLimiter::default()),
+            bound_addrs: Arc::default(),
+            config_path: Arc::from(config_path()),
+        }
+    }
+
+    /// Use the configuration file at `path` instead of [`config_path`].
+    #[must_use]
+    pub fn with_config_path(self, path: impl Into<PathBuf>) -> Self {
+        Self {
+            config_path: Arc::from(path.into()),
+            ..self
         }
     }
 
@@ -64,9 +333,71 @@ impl AppState {
     pub fn get_config(&self) -> AppConfig {
         self.config.read().clone()
     }
+
+    /// Borrow the configuration without cloning it, for hot paths such as resolving a query.
+    ///
+    /// Configuration reloads wait while the guard is held, so drop it before any `.await`.
+    pub fn read_config(&self) -> RwLockReadGuard<'_, AppConfig> {
+        self.config.read()
+    }
 }
 
 impl Config {
+    /// Read configuration from `REDIRECTOR_*` environment variables, e.g. `REDIRECTOR_PORT`.
+    ///
+    /// # Errors
+    /// If a variable is set to a value that cannot be parsed, e.g. an invalid IP address.
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::from_env_with(|name| env::var_os(name))
+    }
+
+    /// [`Config::from_env`] with the environment variables looked up through `lookup`.
+    pub(crate) fn from_env_with(lookup: impl Fn(&str) -> Option<OsString>) -> anyhow::Result<Self> {
+        fn var<T: FromStr>(
+            lookup: &dyn Fn(&str) -> Option<OsString>,
+            name: &str,
+        ) -> anyhow::Result<Option<T>>
+        where
+            T::Err: Display,
+        {
+            lookup(name)
+                .map(|value| {
+                    let value = value
+                        .into_string()
+                        .map_err(|_| anyhow::anyhow!("{name} is not valid unicode"))?;
+                    value
+                        .parse()
+                        .map_err(|e| anyhow::anyhow!("{name} '{value}' is invalid: {e}"))
+                })
+                .transpose()
+        }
+
+        let lookup: &dyn Fn(&str) -> Option<OsString> = &lookup;
+        Ok(Self {
+            port: var(lookup, "REDIRECTOR_PORT")?,
+            ip: var(lookup, "REDIRECTOR_IP")?,
+            unix_socket: var(lookup, "REDIRECTOR_UNIX_SOCKET")?,
+            cache_dir: var(lookup, "REDIRECTOR_CACHE_DIR")?,
+            bangs_url: var(lookup, "REDIRECTOR_BANGS_URL")?.map(|url| vec![url]),
+            default_search: var(lookup, "REDIRECTOR_DEFAULT_SEARCH")?,
+            search_suggestions: var(lookup, "REDIRECTOR_SEARCH_SUGGESTIONS")?,
+        })
+    }
+
+    /// Fill the options not set here from `fallback`, e.g. CLI options over environment ones.
+    #[must_use]
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            port: self.port.or(fallback.port),
+            ip: self.ip.or(fallback.ip),
+            unix_socket: self.unix_socket.or(fallback.unix_socket),
+            cache_dir: self.cache_dir.or(fallback.cache_dir),
+            bangs_url: self.bangs_url.or(fallback.bangs_url),
+            default_search: self.default_search.or(fallback.default_search),
+            search_suggestions: self.search_suggestions.or(fallback.search_suggestions),
+        }
+    }
+
     /// Merge CLI configuration with an optional file configuration.
     /// CLI options take precedence over file values and fall back on `AppConfig` defaults.
     #[allow(dead_code, clippy::must_use_candidate)]
@@ -75,56 +406,333 @@ impl Config {
         let file = file.unwrap_or(FileConfig {
             port: None,
             ip: None,
+            listen_addrs: None,
+            allow_ephemeral_port: None,
+            addr_file: None,
+            base_path: None,
+            unix_socket: None,
+            cache_dir: None,
+            trusted_proxies: None,
+            allowed_origins: None,
+            admin_token: None,
+            max_user_bangs: None,
             bangs_url: None,
+            builtin_bangs: None,
             default_search: None,
+            default_search_by_lang: None,
             search_suggestions: None,
+            suggestion_provider: None,
+            suggestions_max_bytes: None,
+            enable_suggestions: None,
+            default_template_mode: None,
+            max_bangs_shrink_percent: None,
+            reject_shrunk_bangs: None,
+            strip_words: None,
+            query_param: None,
+            plus_as_space: None,
+            normalize_whitespace: None,
+            strip_unknown_bang: None,
+            no_referrer_redirects: None,
+            redirect_log_level: None,
+            no_query_action: None,
+            redirect_status: None,
+            decode_slashes: None,
+            space_as_plus: None,
+            resolve_fallback_chain: None,
+            rate_limit_per_min: None,
+            rate_limit_all_routes: None,
+            security_headers: None,
             bangs: None,
         });
+        let suggestion_provider = suggestion_provider(
+            self.search_suggestions,
+            file.suggestion_provider,
+            file.search_suggestions,
+        );
         AppConfig {
             port: self.port.or(file.port).unwrap_or(default.port),
             ip: self.ip.or(file.ip).unwrap_or(default.ip),
+            listen_addrs: file.listen_addrs,
+            allow_ephemeral_port: file
+                .allow_ephemeral_port
+                .unwrap_or(default.allow_ephemeral_port),
+            addr_file: file.addr_file,
+            base_path: file.base_path.unwrap_or(default.base_path),
+            unix_socket: self.unix_socket.or(file.unix_socket),
+            cache_dir: self.cache_dir.or(file.cache_dir),
+            trusted_proxies: file.trusted_proxies.unwrap_or_default(),
+            allowed_origins: file.allowed_origins.unwrap_or_default(),
+            admin_token: file.admin_token,
+            max_user_bangs: file.max_user_bangs.unwrap_or(default.max_user_bangs),
             bangs_url: self
                 .bangs_url
                 .or(file.bangs_url)
                 .unwrap_or(default.bangs_url),
+            builtin_bangs: file.builtin_bangs.unwrap_or(default.builtin_bangs),
             default_search: self
                 .default_search
                 .or(file.default_search)
                 .unwrap_or(default.default_search),
-            search_suggestions: self
-                .search_suggestions
-                .or(file.search_suggestions)
-                .unwrap_or(default.search_suggestions),
+            default_search_by_lang: file.default_search_by_lang.unwrap_or_default(),
+            search_suggestions: suggestion_provider.template().to_string(),
+            suggestion_provider,
+            suggestions_max_bytes: file
+                .suggestions_max_bytes
+                .unwrap_or(default.suggestions_max_bytes),
+            enable_suggestions: file
+                .enable_suggestions
+                .unwrap_or(default.enable_suggestions),
+            default_template_mode: file
+                .default_template_mode
+                .unwrap_or(default.default_template_mode),
+            max_bangs_shrink_percent: file
+                .max_bangs_shrink_percent
+                .unwrap_or(default.max_bangs_shrink_percent),
+            reject_shrunk_bangs: file
+                .reject_shrunk_bangs
+                .unwrap_or(default.reject_shrunk_bangs),
+            strip_words: file.strip_words,
+            query_param: file.query_param.unwrap_or(default.query_param),
+            plus_as_space: file.plus_as_space.unwrap_or(default.plus_as_space),
+            normalize_whitespace: file
+                .normalize_whitespace
+                .unwrap_or(default.normalize_whitespace),
+            strip_unknown_bang: file
+                .strip_unknown_bang
+                .unwrap_or(default.strip_unknown_bang),
+            no_referrer_redirects: file
+                .no_referrer_redirects
+                .unwrap_or(default.no_referrer_redirects),
+            redirect_log_level: file
+                .redirect_log_level
+                .unwrap_or(default.redirect_log_level),
+            no_query_action: file.no_query_action.unwrap_or(default.no_query_action),
+            redirect_status: file.redirect_status.unwrap_or(default.redirect_status),
+            decode_slashes: file.decode_slashes.unwrap_or(default.decode_slashes),
+            space_as_plus: file.space_as_plus.unwrap_or(default.space_as_plus),
+            resolve_fallback_chain: file
+                .resolve_fallback_chain
+                .unwrap_or(default.resolve_fallback_chain),
+            rate_limit_per_min: file
+                .rate_limit_per_min
+                .unwrap_or(default.rate_limit_per_min),
+            rate_limit_all_routes: file
+                .rate_limit_all_routes
+                .unwrap_or(default.rate_limit_all_routes),
+            security_headers: file.security_headers.unwrap_or(default.security_headers),
             bangs: file.bangs,
         }
+        .normalized()
     }
 }
 
+/// Pick the suggestion provider: a `search_suggestions` template from the command line or the
+/// environment, then `suggestion_provider` and `search_suggestions` from the file, then Brave.
+fn suggestion_provider(
+    overridden: Option<String>,
+    file_provider: Option<SuggestionProvider>,
+    file_template: Option<String>,
+) -> SuggestionProvider {
+    overridden
+        .map(SuggestionProvider::Custom)
+        .or(file_provider)
+        .or(file_template.map(SuggestionProvider::Custom))
+        .unwrap_or_default()
+}
+
 impl FileConfig {
+    /// Check the file for settings that are ignored in favor of others, which
+    /// [`AppConfig::validate`] can no longer tell once the file is merged.
+    ///
+    /// Returns a description of each problem found, or an empty list if there are none.
+    #[must_use]
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if let (Some(template), Some(provider)) =
+            (&self.search_suggestions, &self.suggestion_provider)
+        {
+            problems.push(format!(
+                "search_suggestions '{template}' is ignored because suggestion_provider is set to '{}', remove one of them",
+                provider.template()
+            ));
+        }
+        problems
+    }
+
     /// Merge CLI configuration with an optional file configuration.
     /// CLI options take precedence over file values.
     #[allow(dead_code, clippy::must_use_candidate)]
     pub fn merge(self, config: Config) -> AppConfig {
+        let suggestion_provider = suggestion_provider(
+            config.search_suggestions,
+            self.suggestion_provider,
+            self.search_suggestions,
+        );
         AppConfig {
             port: config.port.or(self.port).unwrap_or(3000),
             ip: config
                 .ip
                 .or(self.ip)
                 .unwrap_or_else(|| IpAddr::from([0, 0, 0, 0])),
+            listen_addrs: self.listen_addrs,
+            allow_ephemeral_port: self.allow_ephemeral_port.unwrap_or(false),
+            addr_file: self.addr_file,
+            base_path: self.base_path.unwrap_or_default(),
+            unix_socket: config.unix_socket.or(self.unix_socket),
+            cache_dir: config.cache_dir.or(self.cache_dir),
+            trusted_proxies: self.trusted_proxies.unwrap_or_default(),
+            allowed_origins: self.allowed_origins.unwrap_or_default(),
+            admin_token: self.admin_token,
+            max_user_bangs: self.max_user_bangs.unwrap_or(DEFAULT_MAX_USER_BANGS),
             bangs_url: config
                 .bangs_url
                 .or(self.bangs_url)
-                .unwrap_or_else(|| "https://duckduckgo.com/bang.js".to_string()),
+                .unwrap_or_else(|| vec![DEFAULT_BANGS_URL.to_string()]),
+            builtin_bangs: self.builtin_bangs.unwrap_or(true),
             default_search: config
                 .default_search
                 .or(self.default_search)
                 .unwrap_or_else(|| DEFAULT_SEARCH.to_string()),
-            search_suggestions: config
-                .search_suggestions
-                .or(self.search_suggestions)
-                .unwrap_or_else(|| DEFAULT_SEARCH_SUGGESTIONS.to_string()),
+            default_search_by_lang: self.default_search_by_lang.unwrap_or_default(),
+            search_suggestions: suggestion_provider.template().to_string(),
+            suggestion_provider,
+            suggestions_max_bytes: self
+                .suggestions_max_bytes
+                .unwrap_or(DEFAULT_SUGGESTIONS_MAX_BYTES),
+            enable_suggestions: self.enable_suggestions.unwrap_or(true),
+            default_template_mode: self.default_template_mode.unwrap_or_default(),
+            max_bangs_shrink_percent: self
+                .max_bangs_shrink_percent
+                .unwrap_or(DEFAULT_MAX_BANGS_SHRINK_PERCENT),
+            reject_shrunk_bangs: self.reject_shrunk_bangs.unwrap_or(false),
+            strip_words: self.strip_words,
+            query_param: self.query_param.unwrap_or_else(|| "q".to_string()),
+            plus_as_space: self.plus_as_space.unwrap_or(true),
+            normalize_whitespace: self.normalize_whitespace.unwrap_or(true),
+            strip_unknown_bang: self.strip_unknown_bang.unwrap_or(false),
+            no_referrer_redirects: self.no_referrer_redirects.unwrap_or(false),
+            redirect_log_level: self.redirect_log_level.unwrap_or_default(),
+            no_query_action: self.no_query_action.unwrap_or_default(),
+            redirect_status: self.redirect_status.unwrap_or_default(),
+            decode_slashes: self.decode_slashes.unwrap_or(true),
+            space_as_plus: self.space_as_plus.unwrap_or(false),
+            resolve_fallback_chain: self
+                .resolve_fallback_chain
+                .unwrap_or_else(|| DEFAULT_RESOLVE_FALLBACK_CHAIN.to_vec()),
+            rate_limit_per_min: self.rate_limit_per_min.unwrap_or(0),
+            rate_limit_all_routes: self.rate_limit_all_routes.unwrap_or(false),
+            security_headers: self.security_headers.unwrap_or_default(),
             bangs: self.bangs,
         }
+        .normalized()
+    }
+}
+
+impl AppConfig {
+    /// The TCP addresses to serve on: `listen_addrs` if set, otherwise `ip` and `port`.
+    #[must_use]
+    pub fn socket_addrs(&self) -> Vec<SocketAddr> {
+        match &self.listen_addrs {
+            Some(addrs) if !addrs.is_empty() => addrs.clone(),
+            _ => vec![SocketAddr::new(self.ip, self.port)],
+        }
+    }
+
+    /// The `default_search` template for a client preferring `lang`, e.g. `de-AT`.
+    ///
+    /// Tags in `default_search_by_lang` match `lang` itself or any of its prefixes ending at a
+    /// subtag, so `de` matches `de-AT`; the most specific match wins.
+    #[must_use]
+    pub fn default_search_for(&self, lang: Option<&str>) -> &str {
+        lang.and_then(|lang| {
+            self.default_search_by_lang
+                .iter()
+                .filter(|(tag, _)| {
+                    lang.get(..tag.len())
+                        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(tag))
+                        && matches!(lang.as_bytes().get(tag.len()), None | Some(b'-'))
+                })
+                .max_by_key(|(tag, _)| tag.len())
+        })
+        .map_or(&self.default_search, |(_, template)| template)
+    }
+
+    /// Check the configuration for settings that cannot work as intended.
+    ///
+    /// Returns a description of each problem found, or an empty list if there are none.
+    #[must_use]
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let by_lang = self.default_search_by_lang.iter().map(|(lang, template)| {
+            (
+                format!("default_search_by_lang.{lang}"),
+                template,
+                "every search goes to the same page",
+            )
+        });
+        for (name, template, without_placeholder) in [
+            (
+                "default_search".to_string(),
+                &self.default_search,
+                "every search goes to the same page",
+            ),
+            (
+                "search_suggestions".to_string(),
+                &self.search_suggestions,
+                "suggestions are disabled",
+            ),
+        ]
+        .into_iter()
+        .chain(by_lang)
+        {
+            match template.matches("{}").count() {
+                1 => {}
+                0 => problems.push(format!(
+                    "{name} '{template}' has no '{{}}' placeholder for the query, {without_placeholder}"
+                )),
+                _ => problems.push(format!(
+                    "{name} '{template}' has more than one '{{}}' placeholder, the query is inserted at each"
+                )),
+            }
+        }
+        if self.unix_socket.is_none() && !self.allow_ephemeral_port {
+            for addr in self.socket_addrs() {
+                if addr.port() == 0 {
+                    problems.push(format!(
+                        "'{addr}' has port 0, so the server listens on a random free port; set allow_ephemeral_port = true if that is intended"
+                    ));
+                }
+            }
+        }
+        #[cfg(feature = "server")]
+        for (name, value) in &self.security_headers {
+            if axum::http::HeaderName::from_bytes(name.as_bytes()).is_err()
+                || axum::http::HeaderValue::from_str(value).is_err()
+            {
+                problems.push(format!(
+                    "security_headers has an invalid header '{name}: {value}', it is not sent"
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Strip surrounding whitespace from the search templates, e.g. left over from a config file,
+    /// and write `base_path` as `/path`, or empty for the root.
+    fn normalized(mut self) -> Self {
+        for template in [&mut self.default_search, &mut self.search_suggestions] {
+            let trimmed = template.trim();
+            if trimmed.len() != template.len() {
+                *template = trimmed.to_string();
+            }
+        }
+        let base_path = self.base_path.trim().trim_matches('/');
+        self.base_path = if base_path.is_empty() {
+            String::new()
+        } else {
+            format!("/{base_path}")
+        };
+        self
     }
 }
 
@@ -133,28 +741,70 @@ impl Default for AppConfig {
         Self {
             port: 3000,
             ip: IpAddr::from([0, 0, 0, 0]),
-            bangs_url: "https://duckduckgo.com/bang.js".to_string(),
+            listen_addrs: None,
+            allow_ephemeral_port: false,
+            addr_file: None,
+            base_path: String::new(),
+            unix_socket: None,
+            cache_dir: None,
+            trusted_proxies: Vec::new(),
+            allowed_origins: Vec::new(),
+            admin_token: None,
+            max_user_bangs: DEFAULT_MAX_USER_BANGS,
+            bangs_url: vec![DEFAULT_BANGS_URL.to_string()],
+            builtin_bangs: true,
             default_search: DEFAULT_SEARCH.to_string(),
+            default_search_by_lang: HashMap::new(),
             search_suggestions: DEFAULT_SEARCH_SUGGESTIONS.to_string(),
+            suggestion_provider: SuggestionProvider::Brave,
+            suggestions_max_bytes: DEFAULT_SUGGESTIONS_MAX_BYTES,
+            enable_suggestions: true,
+            default_template_mode: TemplateMode::default(),
+            max_bangs_shrink_percent: DEFAULT_MAX_BANGS_SHRINK_PERCENT,
+            reject_shrunk_bangs: false,
+            strip_words: None,
+            query_param: "q".to_string(),
+            plus_as_space: true,
+            normalize_whitespace: true,
+            strip_unknown_bang: false,
+            no_referrer_redirects: false,
+            redirect_log_level: LogLevel::Info,
+            no_query_action: NoQueryAction::BangsPage,
+            redirect_status: RedirectStatus::SeeOther,
+            decode_slashes: true,
+            space_as_plus: false,
+            resolve_fallback_chain: DEFAULT_RESOLVE_FALLBACK_CHAIN.to_vec(),
+            rate_limit_per_min: 0,
+            rate_limit_all_routes: false,
+            security_headers: HashMap::new(),
             bangs: None,
         }
     }
 }
 
+#[cfg(feature = "cli")]
 impl From<Cli> for Config {
     fn from(cli: Cli) -> Self {
         match cli.command {
-            Some(SubCommand::Serve { port, ip }) => Self {
+            Some(SubCommand::Serve {
+                port,
+                ip,
+                unix_socket,
+            }) => Self {
                 port,
                 ip,
-                bangs_url: cli.bangs_url,
+                unix_socket,
+                cache_dir: cli.cache_dir,
+                bangs_url: (!cli.bangs_url.is_empty()).then_some(cli.bangs_url),
                 default_search: cli.default_search,
                 search_suggestions: cli.search_suggestions,
             },
-            Some(SubCommand::Resolve { query: _ }) => Self {
+            Some(SubCommand::Resolve { .. }) => Self {
                 port: None,
                 ip: None,
-                bangs_url: cli.bangs_url,
+                unix_socket: None,
+                cache_dir: cli.cache_dir,
+                bangs_url: (!cli.bangs_url.is_empty()).then_some(cli.bangs_url),
                 default_search: cli.default_search,
                 search_suggestions: cli.search_suggestions,
             },
@@ -164,9 +814,10 @@ impl From<Cli> for Config {
 }
 
 /// Reloads configuration from disk while preserving CLI options.
+#[cfg(feature = "server")]
 pub async fn reload_config(app_state: &AppState) {
     // Get new file config
-    let file_config = get_file_config();
+    let file_config = get_file_config(&app_state.config_path);
 
     if let Some(config) = file_config {
         let mut config_clone = {
@@ -193,16 +844,81 @@ pub async fn reload_config(app_state: &AppState) {
     }
 }
 
-pub fn get_file_config() -> Option<FileConfig> {
-    let home_dir = env::var("HOME").unwrap_or_else(|_| ".".to_string());
-    let config_path = Path::new(&home_dir)
-        .join(".config")
-        .join("redirector")
-        .join("config.toml");
+/// Reloads only the configured `[[bangs]]` from disk, without fetching the remote bang list.
+///
+/// Triggers that are no longer configured are removed from the bang cache; a remote bang they
+/// used to override comes back with the next bang list update.
+///
+/// Returns whether a configuration file was found and reloaded.
+pub fn reload_bangs_only(app_state: &AppState) -> bool {
+    let Some(file_config) = get_file_config(&app_state.config_path) else {
+        debug!("No configuration file found, nothing was changed.");
+        return false;
+    };
+    let bangs = file_config.bangs;
+
+    let mut config = app_state.config.write();
+    let mut cache = BANG_CACHE.write();
+    for old in config.bangs.iter().flatten() {
+        let still_configured = bangs
+            .iter()
+            .flatten()
+            .any(|bang| bang.trigger.eq_ignore_ascii_case(&old.trigger));
+        if !still_configured {
+            cache.remove(&old.key());
+        }
+    }
+    for bang in bangs.iter().flatten() {
+        cache.insert(bang.key(), bang.configured());
+    }
+    drop(cache);
+    crate::reindex_triggers();
+    config.bangs = bangs;
+    drop(config);
+
+    info!("Configured bangs reloaded successfully");
+    true
+}
+
+/// Path of the configuration file, `redirector/config.toml` in the configuration directory of
+/// the platform: `%APPDATA%` on Windows and `$XDG_CONFIG_HOME`, or `~/.config` if it is not set,
+/// elsewhere.
+#[must_use]
+pub fn config_path() -> PathBuf {
+    config_path_in(cfg!(windows), |key| {
+        env::var_os(key).filter(|value| !value.is_empty())
+    })
+}
+
+/// [`config_path`] with the environment variables looked up through `var`.
+///
+/// Without a usable variable, Windows falls back to the current directory and everything else to
+/// `/etc`, as services started without a home directory usually keep their configuration there.
+pub(crate) fn config_path_in(windows: bool, var: impl Fn(&str) -> Option<OsString>) -> PathBuf {
+    let config_dir = if windows {
+        var("APPDATA")
+            .map(PathBuf::from)
+            .or_else(|| {
+                var("USERPROFILE")
+                    .map(|profile| Path::new(&profile).join("AppData").join("Roaming"))
+            })
+            .unwrap_or_else(|| PathBuf::from("."))
+    } else {
+        // Relative paths in `XDG_CONFIG_HOME` are invalid and must be ignored.
+        var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| var("HOME").map(|home| Path::new(&home).join(".config")))
+            .unwrap_or_else(|| PathBuf::from("/etc"))
+    };
+    config_dir.join("redirector").join("config.toml")
+}
 
+/// Read the configuration file at `config_path`, usually [`config_path`].
+pub fn get_file_config(config_path: &Path) -> Option<FileConfig> {
     // Attempt to load the file configuration if it exists.
     if config_path.exists() {
-        match read_to_string(&config_path) {
+        match read_to_string(config_path) {
             Ok(contents) => match toml::from_str::<FileConfig>(&contents) {
                 Ok(conf) => Some(conf),
                 Err(e) => {
@@ -229,41 +945,38 @@ pub fn get_file_config() -> Option<FileConfig> {
     }
 }
 
-#[allow(clippy::cognitive_complexity)]
-pub fn append_file_config(bang: Bang) {
-    let home_dir = env::var("HOME").unwrap_or_else(|_| ".".to_string());
-    let config_path = Path::new(&home_dir)
-        .join(".config")
-        .join("redirector")
-        .join("config.toml");
-
+/// Append `bang` to the `[[bangs]]` of the configuration file at `config_path`.
+pub fn append_file_config(config_path: &Path, bang: Bang) {
     // Attempt to load the file configuration if it exists.
     if config_path.exists() {
-        match read_to_string(&config_path) {
+        match read_to_string(config_path) {
             Ok(mut contents) => {
-                // append the new bang to the config file
-                // TODO: dont use unwrap
-                write!(contents, "\n[[bangs]]").unwrap();
-                write!(contents, "\ntrigger = \"{}\"", bang.trigger).unwrap();
-                write!(contents, "\nurl_template = \"{}\"", bang.url_template).unwrap();
-                if let Some(category) = bang.category {
-                    write!(contents, "\ncategory = \"{category}\"").unwrap();
-                }
-                if let Some(domain) = bang.domain {
-                    write!(contents, "\ndomain = \"{domain}\"").unwrap();
-                }
-                if let Some(relevance) = bang.relevance {
-                    write!(contents, "\nrelevance = {relevance}").unwrap();
+                // Serialize the bang as a `[[bangs]]` table of its own so every field is
+                // written with proper escaping, and append it to keep the rest of the file as is.
+                let table = toml::Table::from_iter([(
+                    "bangs".to_string(),
+                    toml::Value::Array(vec![match toml::Value::try_from(&bang) {
+                        Ok(value) => value,
+                        Err(e) => {
+                            error!("Failed to serialize bang '{}': {}", bang.trigger, e);
+                            return;
+                        }
+                    }]),
+                )]);
+                let bang_table = match toml::to_string(&table) {
+                    Ok(bang_table) => bang_table,
+                    Err(e) => {
+                        error!("Failed to serialize bang '{}': {}", bang.trigger, e);
+                        return;
+                    }
+                };
+                if !contents.is_empty() && !contents.ends_with('\n') {
+                    contents.push('\n');
                 }
-                if let Some(short_name) = bang.short_name {
-                    write!(contents, "\nshort_name = \"{short_name}\"").unwrap();
-                }
-                if let Some(subcategory) = bang.subcategory {
-                    write!(contents, "\nsubcategory = \"{subcategory}\"").unwrap();
-                }
-                writeln!(contents).unwrap();
+                contents.push('\n');
+                contents.push_str(&bang_table);
 
-                if let Err(e) = std::fs::write(&config_path, contents) {
+                if let Err(e) = std::fs::write(config_path, contents) {
                     error!(
                         "Failed to write to configuration file at {}: {}",
                         config_path.display(),
@@ -285,3 +998,264 @@ pub fn append_file_config(bang: Bang) {
         debug!("Configuration file not found at {}.", config_path.display());
     }
 }
+
+/// Replace all `[[bangs]]` in the configuration file at `config_path` with `bangs`, keeping the
+/// other settings.
+///
+/// Unlike [`append_file_config`] this rewrites the whole file, so comments and formatting are lost.
+pub fn rewrite_file_bangs(config_path: &Path, bangs: &[Bang]) {
+    let mut table = match read_to_string(config_path) {
+        Ok(contents) => match toml::from_str::<toml::Table>(&contents) {
+            Ok(table) => table,
+            Err(e) => {
+                error!(
+                    "Failed to parse configuration file at {}: {}",
+                    config_path.display(),
+                    e
+                );
+                return;
+            }
+        },
+        Err(e) => {
+            error!(
+                "Failed to read configuration file at {}: {}",
+                config_path.display(),
+                e
+            );
+            return;
+        }
+    };
+
+    match toml::Value::try_from(bangs) {
+        Ok(value) => {
+            table.insert("bangs".to_string(), value);
+        }
+        Err(e) => {
+            error!("Failed to serialize bangs: {}", e);
+            return;
+        }
+    }
+
+    match toml::to_string(&table) {
+        Ok(contents) => {
+            if let Err(e) = std::fs::write(config_path, contents) {
+                error!(
+                    "Failed to write to configuration file at {}: {}",
+                    config_path.display(),
+                    e
+                );
+            } else {
+                info!("Configuration file updated successfully.");
+            }
+        }
+        Err(e) => error!("Failed to serialize configuration: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_path() {
+        let env = |vars: &'static [(&str, &str)]| {
+            move |key: &str| {
+                vars.iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, value)| OsString::from(value))
+            }
+        };
+        let path = |parts: &[&str]| parts.iter().collect::<PathBuf>();
+
+        assert_eq!(
+            config_path_in(false, env(&[("HOME", "/home/user")])),
+            path(&["/home/user", ".config", "redirector", "config.toml"])
+        );
+        assert_eq!(
+            config_path_in(
+                false,
+                env(&[("XDG_CONFIG_HOME", "/srv/config"), ("HOME", "/home/user")])
+            ),
+            path(&["/srv/config", "redirector", "config.toml"])
+        );
+        assert_eq!(
+            config_path_in(
+                false,
+                env(&[("XDG_CONFIG_HOME", "config"), ("HOME", "/home/user")])
+            ),
+            path(&["/home/user", ".config", "redirector", "config.toml"])
+        );
+        assert_eq!(
+            config_path_in(false, env(&[])),
+            path(&["/etc", "redirector", "config.toml"])
+        );
+        assert_eq!(
+            config_path_in(
+                true,
+                env(&[
+                    ("APPDATA", r"C:\Users\user\AppData\Roaming"),
+                    ("HOME", "/home/user"),
+                ])
+            ),
+            path(&[
+                r"C:\Users\user\AppData\Roaming",
+                "redirector",
+                "config.toml"
+            ])
+        );
+        assert_eq!(
+            config_path_in(true, env(&[("USERPROFILE", r"C:\Users\user")])),
+            path(&[
+                r"C:\Users\user",
+                "AppData",
+                "Roaming",
+                "redirector",
+                "config.toml"
+            ])
+        );
+        assert_eq!(
+            config_path_in(true, env(&[])),
+            path(&[".", "redirector", "config.toml"])
+        );
+    }
+
+    #[test]
+    fn test_env_config_layer() {
+        let env = |vars: &'static [(&str, &str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.into())
+            }
+        };
+        let env_config = Config::from_env_with(env(&[
+            ("REDIRECTOR_PORT", "8080"),
+            ("REDIRECTOR_IP", "127.0.0.1"),
+            ("REDIRECTOR_DEFAULT_SEARCH", "https://env.example/?q={}"),
+        ]))
+        .unwrap();
+        let file = FileConfig {
+            port: Some(9000),
+            default_search: Some("https://file.example/?q={}".to_string()),
+            search_suggestions: Some("https://file.example/suggest?q={}".to_string()),
+            ..FileConfig::default()
+        };
+        let cli = Config {
+            default_search: Some("https://cli.example/?q={}".to_string()),
+            ..Config::default()
+        };
+
+        let config = file.merge(cli.or(env_config));
+        // CLI over env over file over defaults.
+        assert_eq!(config.default_search, "https://cli.example/?q={}");
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.ip, IpAddr::from([127, 0, 0, 1]));
+        assert_eq!(
+            config.search_suggestions,
+            "https://file.example/suggest?q={}"
+        );
+        assert_eq!(config.bangs_url, AppConfig::default().bangs_url);
+        assert_eq!(
+            config.suggestion_provider,
+            SuggestionProvider::Custom("https://file.example/suggest?q={}".to_string())
+        );
+
+        let error = Config::from_env_with(env(&[("REDIRECTOR_IP", "not an ip")]))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("REDIRECTOR_IP"), "{error}");
+    }
+
+    #[test]
+    fn test_suggestion_provider_config() {
+        let default = FileConfig::default().merge(Config::default());
+        assert_eq!(default.suggestion_provider, SuggestionProvider::Brave);
+        assert_eq!(
+            default.search_suggestions,
+            AppConfig::default().search_suggestions
+        );
+
+        let file = r#"
+            suggestion_provider = "duckduckgo"
+            search_suggestions = "https://ignored.example/?q={}"
+        "#;
+        let problems = toml::from_str::<FileConfig>(file).unwrap().validate();
+        assert_eq!(problems.len(), 1);
+        assert!(
+            problems[0]
+                .starts_with("search_suggestions 'https://ignored.example/?q={}' is ignored")
+        );
+        let config = toml::from_str::<FileConfig>(file)
+            .unwrap()
+            .merge(Config::default());
+        assert_eq!(config.suggestion_provider, SuggestionProvider::DuckDuckGo);
+        assert_eq!(config.search_suggestions, "https://duckduckgo.com/ac/?q={}");
+
+        // A template from the command line replaces the provider from the file.
+        let cli = Config {
+            search_suggestions: Some("https://cli.example/?q={}".to_string()),
+            ..Config::default()
+        };
+        let config = toml::from_str::<FileConfig>(file).unwrap().merge(cli);
+        assert_eq!(
+            config.suggestion_provider,
+            SuggestionProvider::Custom("https://cli.example/?q={}".to_string())
+        );
+
+        let file: FileConfig =
+            toml::from_str(r#"suggestion_provider = { custom = "https://custom.example/?s={}" }"#)
+                .unwrap();
+        assert!(file.validate().is_empty());
+        let config = file.merge(Config::default());
+        assert_eq!(config.search_suggestions, "https://custom.example/?s={}");
+    }
+
+    #[test]
+    fn test_validate_search_templates() {
+        assert!(AppConfig::default().validate().is_empty());
+
+        let missing = AppConfig {
+            default_search: "https://example.com/search".to_string(),
+            ..AppConfig::default()
+        };
+        let problems = missing.validate();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("default_search"));
+        assert!(problems[0].contains("no '{}' placeholder"));
+
+        let multiple = AppConfig {
+            search_suggestions: "https://example.com/?q={}&again={}".to_string(),
+            ..AppConfig::default()
+        };
+        let problems = multiple.validate();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("search_suggestions"));
+        assert!(problems[0].contains("more than one"));
+
+        let file = FileConfig {
+            default_search: Some("  https://example.com/?q={}\n".to_string()),
+            ..FileConfig::default()
+        };
+        let config = file.merge(Config::default());
+        assert_eq!(config.default_search, "https://example.com/?q={}");
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn test_validate_ephemeral_port() {
+        let mut config = AppConfig {
+            port: 0,
+            ..AppConfig::default()
+        };
+        let problems = config.validate();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("allow_ephemeral_port"));
+
+        config.allow_ephemeral_port = true;
+        assert!(config.validate().is_empty());
+
+        config.allow_ephemeral_port = false;
+        config.unix_socket = Some("/run/redirector.sock".into());
+        assert!(config.validate().is_empty());
+    }
+}
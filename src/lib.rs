@@ -1,20 +1,73 @@
 pub mod bang;
+#[cfg(feature = "cli")]
 pub mod cli;
 pub mod config;
+pub mod metrics;
+#[cfg(feature = "server")]
+pub mod proxy;
+#[cfg(feature = "network")]
+pub mod prune;
+pub mod rate_limit;
+#[cfg(feature = "network")]
+pub mod server;
+pub mod stats;
 
-use crate::bang::Bang;
-use crate::config::AppConfig;
+use crate::bang::{
+    Bang, BangMethod, BangSource, BangSourceKind, PLACEHOLDERS, RedirectStatus,
+    TRIGGER_PLACEHOLDER, WILDCARD_TRIGGER,
+};
+use crate::config::{AppConfig, FallbackStep, TemplateMode};
 use memchr::memchr;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use rustc_hash::{FxBuildHasher, FxHashMap};
+use serde::Serialize;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
+use std::fmt::Display;
+use std::hash::BuildHasher;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
+#[cfg(feature = "server")]
 use std::time::{Duration, Instant};
-use tokio::time::interval;
-use tracing::{debug, error};
+#[cfg(feature = "server")]
+use tracing::error;
+use tracing::{debug, info, warn};
+use url::Url;
 
-pub static BANG_CACHE: LazyLock<RwLock<HashMap<String, String>>> =
-    LazyLock::new(|| RwLock::new(HashMap::new()));
+/// Bangs keyed by trigger. Triggers are short ASCII keys, so the cache uses the fast
+/// non-cryptographic `FxHash` rather than `SipHash`.
+pub type BangMap = FxHashMap<String, Bang>;
+
+/// All active bangs, keyed by trigger.
+pub static BANG_CACHE: LazyLock<RwLock<BangMap>> =
+    LazyLock::new(|| RwLock::new(BangMap::default()));
+
+/// The keys of [`BANG_CACHE`] in order, so bangs being typed are completed without scanning
+/// the whole cache. Rebuilt by [`reindex_triggers`] whenever the cache changes.
+static TRIGGER_INDEX: LazyLock<RwLock<BTreeSet<String>>> = LazyLock::new(RwLock::default);
+/// How long fetching the bang list may take before giving up.
+#[cfg(feature = "network")]
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A few common bangs available alongside the bang list, unless `builtin_bangs` is turned off.
+/// Fetched and configured bangs override them.
+static BUILTIN_BANGS: LazyLock<Vec<Bang>> = LazyLock::new(|| {
+    let BangSource(bangs) = serde_json::from_str(include_str!("../res/builtin_bangs.json"))
+        .expect("The built-in bang list is valid");
+    bangs
+        .into_iter()
+        .map(|bang| Bang {
+            source: BangSourceKind::Builtin,
+            ..bang
+        })
+        .collect()
+});
+
+/// The search engine used by the `embedded` fallback step, independent of the configuration.
+const EMBEDDED_SEARCH: &str = "https://duckduckgo.com/?q={}";
+
+#[cfg(feature = "server")]
 static LAST_UPDATE: LazyLock<RwLock<Instant>> = LazyLock::new(|| RwLock::new(Instant::now()));
 
 /// Get the bang command from the query.
@@ -32,10 +85,7 @@ pub fn get_bang(query: &str) -> Option<&str> {
 
     // Check for bang at start (common case)
     if bytes[0] == b'!' {
-        let mut end = 1;
-        while end < len && bytes[end] != b' ' {
-            end += 1;
-        }
+        let end = memchr(b' ', &bytes[1..]).map_or(len, |e| 1 + e);
         // Valid bang needs at least one character after '!'
         if end > 1 {
             return Some(&query[0..end]);
@@ -61,73 +111,644 @@ pub fn get_bang(query: &str) -> Option<&str> {
     None
 }
 
+/// How a query was resolved.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MatchKind {
+    /// A bang in the query matched a known trigger.
+    BangMatched { trigger: String },
+    /// The query contained no bang and went to the default search.
+    DefaultSearch,
+    /// The query contained a bang that is not known, so it went to the default search.
+    DefaultBang,
+    /// The resolved URL was malformed and the search engine built into the binary was used.
+    EmbeddedFallback,
+    /// Every step of the fallback chain failed, so there is no URL to redirect to.
+    Unresolved,
+}
+
+/// The result of resolving a query, including which bang (if any) was used.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ResolveOutcome {
+    /// The URL the query resolves to.
+    pub url: String,
+    /// How the URL was chosen.
+    #[serde(flatten)]
+    pub kind: MatchKind,
+    /// Where the matched bang came from, or `Default` if none was used.
+    pub source: BangSourceKind,
+    /// Whether the matched bang asked to be kept out of logs and usage stats.
+    #[serde(skip)]
+    pub no_log: bool,
+    /// Form fields to POST to `url` instead of redirecting to it, for bangs with `method = "post"`.
+    #[serde(skip)]
+    pub form: Option<Vec<(String, String)>>,
+    /// Whether the matched bang asked for the destination to be confirmed before leaving.
+    #[serde(skip)]
+    pub confirm: bool,
+    /// The redirect status the matched bang asked for, overriding `redirect_status`.
+    #[serde(skip)]
+    pub status: Option<RedirectStatus>,
+}
+
+impl ResolveOutcome {
+    /// The trigger of the matched bang, if any.
+    #[must_use]
+    pub fn trigger(&self) -> Option<&str> {
+        match &self.kind {
+            MatchKind::BangMatched { trigger } => Some(trigger),
+            MatchKind::DefaultSearch
+            | MatchKind::DefaultBang
+            | MatchKind::EmbeddedFallback
+            | MatchKind::Unresolved => None,
+        }
+    }
+}
+
 #[allow(clippy::inline_always)]
 #[inline(always)]
 #[must_use]
 pub fn resolve(app_config: &AppConfig, query: &str) -> String {
+    resolve_detailed(app_config, query).url
+}
+
+/// Resolve a query and report which bang, if any, was used to build the URL.
+#[must_use]
+pub fn resolve_detailed(app_config: &AppConfig, query: &str) -> ResolveOutcome {
+    resolve_with(&BANG_CACHE.read(), app_config, query)
+}
+
+/// Resolve several queries in order, taking the bang cache read lock only once.
+#[must_use]
+pub fn resolve_batch<S: AsRef<str>>(app_config: &AppConfig, queries: &[S]) -> Vec<ResolveOutcome> {
+    let cache = BANG_CACHE.read();
+    queries
+        .iter()
+        .map(|query| resolve_with(&cache, app_config, query.as_ref()))
+        .collect()
+}
+
+/// The most bang triggers suggested for a single query.
+const MAX_BANG_SUGGESTIONS: usize = 10;
+
+/// Complete a bang that is still being typed at the end of `query`, e.g. `rust !g`.
+///
+/// Returns `None` when the last word is not a bang, or the query ends with whitespace, so the
+/// bang is complete. Otherwise returns the query with the bang replaced by each matching
+/// trigger, shortest triggers first.
+#[must_use]
+pub fn bang_suggestions(query: &str) -> Option<Vec<String>> {
+    bang_suggestions_in(&BANG_CACHE.read(), &TRIGGER_INDEX.read(), query)
+}
+
+/// [`bang_suggestions`] from the given bang cache and its [`index_triggers`].
+fn bang_suggestions_in(
+    cache: &BangMap,
+    index: &BTreeSet<String>,
+    query: &str,
+) -> Option<Vec<String>> {
+    if query.ends_with(char::is_whitespace) {
+        return None;
+    }
+    let start = query.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+    let prefix = query[start..].strip_prefix('!')?.to_lowercase();
+
+    // The keys starting with the prefix are adjacent in the index. Only the shortest of them are
+    // kept, so the others never need sorting.
+    let mut shortest = BinaryHeap::with_capacity(MAX_BANG_SUGGESTIONS + 1);
+    for key in index
+        .range::<str, _>((Bound::Included(prefix.as_str()), Bound::Unbounded))
+        .take_while(|key| key.starts_with(&prefix))
+    {
+        shortest.push((key.len(), key));
+        if shortest.len() > MAX_BANG_SUGGESTIONS {
+            shortest.pop();
+        }
+    }
+
+    Some(
+        shortest
+            .into_sorted_vec()
+            .into_iter()
+            .filter_map(|(_, key)| cache.get(key))
+            .map(|bang| format!("{}!{}", &query[..start], bang.trigger))
+            .collect(),
+    )
+}
+
+/// The keys of `cache` that can be completed, in order.
+fn index_triggers(cache: &BangMap) -> BTreeSet<String> {
+    cache
+        .keys()
+        .filter(|key| *key != WILDCARD_TRIGGER)
+        .cloned()
+        .collect()
+}
+
+/// Rebuild the index [`bang_suggestions`] looks triggers up in from [`BANG_CACHE`].
+///
+/// Call after changing the cache other than through [`update_cache`], [`update_bangs`] or
+/// [`load_cached_bangs`], which keep the index up to date themselves.
+pub fn reindex_triggers() {
+    let index = index_triggers(&BANG_CACHE.read());
+    *TRIGGER_INDEX.write() = index;
+}
+
+/// A breakdown of how a query was resolved, for debugging unexpected redirects.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// The query as resolved, after `strip_words` were removed.
+    pub query: String,
+    #[serde(flatten)]
+    pub outcome: ResolveOutcome,
+    /// The URL template of the matched bang, with the typed trigger filled in for the wildcard
+    /// bang.
+    pub template: Option<String>,
+    /// The search term left after removing the bang, as inserted into the template.
+    pub encoded_term: Option<String>,
+    /// How the search term was put into the template.
+    pub placement: Option<TermPlacement>,
+}
+
+/// Resolve a query and explain the decision: which bang matched, its template, the encoded
+/// search term and how the term was put into the template.
+#[must_use]
+pub fn explain(app_config: &AppConfig, query: &str) -> Explanation {
+    explain_in(&BANG_CACHE.read(), app_config, query)
+}
+
+fn explain_in(cache: &BangMap, app_config: &AppConfig, query: &str) -> Explanation {
+    let outcome = resolve_with(cache, app_config, query);
+    let query = match &app_config.strip_words {
+        Some(words) if !words.is_empty() => strip_words(query, words).into_owned(),
+        _ => query.to_string(),
+    };
+    let matched = outcome
+        .trigger()
+        .and_then(|trigger| Some((get_bang(&query)?, cache.get(trigger)?)));
+    let (template, encoded_term, placement) = match matched {
+        Some((bang, bang_entry)) => (
+            // The wildcard bang's template as used, with the typed trigger in place of `{t}`.
+            Some(if bang_entry.trigger == WILDCARD_TRIGGER {
+                let typed = urlencoding::encode(&bang[1..].to_ascii_lowercase()).into_owned();
+                bang_entry.url_template.replace(TRIGGER_PLACEHOLDER, &typed)
+            } else {
+                bang_entry.url_template.clone()
+            }),
+            Some(encode_term(
+                app_config,
+                bang_entry,
+                &remove_bang(&query, bang),
+            )),
+            Some(term_placement(app_config, bang_entry)),
+        ),
+        None => (None, None, None),
+    };
+    Explanation {
+        query,
+        outcome,
+        template,
+        encoded_term,
+        placement,
+    }
+}
+
+/// Resolve a query against the given bang cache instead of the global [`BANG_CACHE`], walking
+/// the configured fallback chain if the result cannot be redirected to.
+///
+/// The cache is keyed by lowercase trigger, see [`Bang::key`]. This allows embedding the
+/// resolver with isolated bang sets, e.g. one per tenant.
+#[must_use]
+pub fn resolve_with<S: BuildHasher>(
+    cache: &HashMap<String, Bang, S>,
+    app_config: &AppConfig,
+    query: &str,
+) -> ResolveOutcome {
+    resolve_in(cache, app_config, &app_config.default_search, query)
+}
+
+/// Resolve a query for a client preferring `lang`, e.g. `de-AT` from `Accept-Language`, using
+/// the matching `default_search_by_lang` template for searches without a bang.
+#[must_use]
+pub fn resolve_with_lang(
+    app_config: &AppConfig,
+    query: &str,
+    lang: Option<&str>,
+) -> ResolveOutcome {
+    let default_search = app_config.default_search_for(lang);
+    resolve_in(&BANG_CACHE.read(), app_config, default_search, query)
+}
+
+fn resolve_in<S: BuildHasher>(
+    cache: &HashMap<String, Bang, S>,
+    app_config: &AppConfig,
+    default_search: &str,
+    query: &str,
+) -> ResolveOutcome {
+    let query = match &app_config.strip_words {
+        Some(words) if !words.is_empty() => strip_words(query, words),
+        _ => Cow::Borrowed(query),
+    };
+    let outcome = resolve_unchecked(cache, app_config, default_search, &query);
+    if is_redirectable(&outcome.url) {
+        outcome
+    } else {
+        fall_back(app_config, default_search, &query, &outcome)
+    }
+}
+
+/// Resolve a query without checking that the resulting URL is well-formed.
+fn resolve_unchecked<S: BuildHasher>(
+    cache: &HashMap<String, Bang, S>,
+    app_config: &AppConfig,
+    default_search: &str,
+    query: &str,
+) -> ResolveOutcome {
     if query.is_empty() {
-        return app_config.default_search.replace("{}", "");
+        return ResolveOutcome {
+            url: default_search.replace("{}", ""),
+            kind: MatchKind::DefaultSearch,
+            source: BangSourceKind::Default,
+            no_log: false,
+            form: None,
+            confirm: false,
+            status: None,
+        };
     }
 
     let bytes = query.as_bytes();
 
     // Fastest path for most common case - single-word plain queries
-    if bytes[0] != b'!' {
-        // Quick check for spaces without using contains()
-        let mut has_space = false;
-        for &b in bytes {
-            if b == b' ' {
-                has_space = true;
-                break;
-            }
+    if bytes[0] != b'!' && memchr(b' ', bytes).is_none() {
+        return ResolveOutcome {
+            url: default_search.replace("{}", &urlencoding::encode(query)),
+            kind: MatchKind::DefaultSearch,
+            source: BangSourceKind::Default,
+            no_log: false,
+            form: None,
+            confirm: false,
+            status: None,
+        };
+    }
+
+    let bang = get_bang(query);
+    if let Some(bang) = bang {
+        let key_lower = bang[1..].to_ascii_lowercase();
+
+        // An exact trigger wins over the wildcard bang.
+        let matched = cache.get(&key_lower).map(|bang_entry| (bang_entry, false));
+        let matched = matched.or_else(|| {
+            let bang_entry = cache.get(WILDCARD_TRIGGER)?;
+            Some((bang_entry, true))
+        });
+        if let Some((bang_entry, wildcard)) = matched {
+            let url_template = if wildcard {
+                Cow::Owned(
+                    bang_entry
+                        .url_template
+                        .replace(TRIGGER_PLACEHOLDER, &urlencoding::encode(&key_lower)),
+                )
+            } else {
+                Cow::Borrowed(&bang_entry.url_template)
+            };
+            let remainder = remove_bang(query, bang);
+            let encoded_term = encode_term(app_config, bang_entry, &remainder);
+            let form = (bang_entry.method == Some(BangMethod::Post))
+                .then(|| post_fields(bang_entry, &search_term(app_config, &remainder)));
+
+            // Template handling
+            let url = match term_placement(app_config, bang_entry) {
+                TermPlacement::Placeholder => fill_placeholders(&url_template, &encoded_term),
+                TermPlacement::Append => append_term(&url_template, &encoded_term),
+                TermPlacement::Verbatim => url_template.to_string(),
+            };
+            let trigger = if wildcard {
+                WILDCARD_TRIGGER.to_string()
+            } else {
+                key_lower
+            };
+            return ResolveOutcome {
+                url,
+                kind: MatchKind::BangMatched { trigger },
+                source: bang_entry.source,
+                no_log: bang_entry.no_log.unwrap_or(false),
+                form,
+                confirm: bang_entry.confirm.unwrap_or(false),
+                status: bang_entry.status,
+            };
         }
+    }
 
-        if !has_space {
-            return app_config
-                .default_search
-                .replace("{}", &urlencoding::encode(query));
+    // Default fallback
+    let stripped;
+    let query = match bang {
+        Some(bang) if app_config.strip_unknown_bang => {
+            stripped = remove_bang(query, bang);
+            stripped.as_str()
         }
+        _ => query,
+    };
+    let query = if app_config.normalize_whitespace {
+        normalize_whitespace(query)
+    } else {
+        Cow::Borrowed(query)
+    };
+    ResolveOutcome {
+        url: default_search.replace("{}", &urlencoding::encode(&query)),
+        kind: if bang.is_some() {
+            MatchKind::DefaultBang
+        } else {
+            MatchKind::DefaultSearch
+        },
+        source: BangSourceKind::Default,
+        no_log: false,
+        form: None,
+        confirm: false,
+        status: None,
     }
+}
 
-    if let Some(bang) = get_bang(query) {
-        let cache = BANG_CACHE.read();
-        let key_lower = bang[1..].to_ascii_lowercase();
+/// Remove `bang`, a slice of `query`, along with one of the spaces around it, so removing a bang
+/// from the middle of a query leaves a single space. Spaces elsewhere in the query are kept.
+fn remove_bang(query: &str, bang: &str) -> String {
+    let start = bang.as_ptr() as usize - query.as_ptr() as usize;
+    let before = &query[..start];
+    let before = before.strip_suffix(' ').unwrap_or(before);
+    [before, &query[start + bang.len()..]].concat()
+}
 
-        if let Some(url_template) = cache.get(&key_lower) {
-            let replaced = query.replacen(bang, "", 1);
-            let search_term = replaced.trim();
-            let mut encoded_term = urlencoding::encode(search_term);
+/// Trim `term` and collapse each run of whitespace in it to a single space.
+fn normalize_whitespace(term: &str) -> Cow<'_, str> {
+    let trimmed = term.trim();
+    let mut previous_space = false;
+    let is_normalized = trimmed.chars().all(|c| {
+        let is_run = c.is_whitespace() && (c != ' ' || previous_space);
+        previous_space = c == ' ';
+        !is_run
+    });
+    if is_normalized {
+        Cow::Borrowed(trimmed)
+    } else {
+        Cow::Owned(trimmed.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+}
 
-            // Fix slashes once in the encoded term
-            if encoded_term.contains("%2F") {
-                encoded_term = Cow::from(encoded_term.replace("%2F", "/"));
-            }
+/// Try each step of `resolve_fallback_chain` for a query whose resolved URL was malformed.
+fn fall_back(
+    app_config: &AppConfig,
+    default_search: &str,
+    query: &str,
+    failed: &ResolveOutcome,
+) -> ResolveOutcome {
+    warn!(
+        trigger = failed.trigger(),
+        "Query resolved to a malformed URL, falling back."
+    );
+    // Search for the rest of the query, as for a bang that matches no trigger.
+    let bang = get_bang(query);
+    let remainder = bang.map_or_else(|| query.to_string(), |bang| remove_bang(query, bang));
+    let remainder = if app_config.normalize_whitespace {
+        normalize_whitespace(&remainder)
+    } else {
+        Cow::Borrowed(remainder.as_str())
+    };
+    let term = urlencoding::encode(&remainder);
+    for step in &app_config.resolve_fallback_chain {
+        let (url, kind) = match step {
+            FallbackStep::DefaultSearch => (
+                default_search.replace("{}", &term),
+                if bang.is_some() {
+                    MatchKind::DefaultBang
+                } else {
+                    MatchKind::DefaultSearch
+                },
+            ),
+            FallbackStep::Embedded => (
+                EMBEDDED_SEARCH.replace("{}", &term),
+                MatchKind::EmbeddedFallback,
+            ),
+            FallbackStep::ErrorPage => break,
+        };
+        if is_redirectable(&url) {
+            return ResolveOutcome {
+                url,
+                kind,
+                source: BangSourceKind::Default,
+                no_log: failed.no_log,
+                form: None,
+                confirm: false,
+                status: None,
+            };
+        }
+    }
+    ResolveOutcome {
+        url: String::new(),
+        kind: MatchKind::Unresolved,
+        source: BangSourceKind::Default,
+        no_log: failed.no_log,
+        form: None,
+        confirm: false,
+        status: None,
+    }
+}
 
-            // Template handling
-            if url_template.contains("{{{s}}}") {
-                let result = url_template.replace("{{{s}}}", &encoded_term);
-                if encoded_term.contains("%2F") {
-                    return result.replace("%2F", "/");
-                }
-                return result;
-            }
+/// Whether a URL is absolute http(s) with a host and can be sent in a `Location` header.
+fn is_redirectable(url: &str) -> bool {
+    let after_scheme = ["https://", "http://"].iter().find_map(|scheme| {
+        url.get(..scheme.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(scheme))
+            .map(|_| &url[scheme.len()..])
+    });
+    after_scheme.is_some_and(|rest| !rest.is_empty() && !rest.starts_with('/'))
+        && !url.bytes().any(|b| b.is_ascii_control())
+}
 
-            // Simple append case
-            let mut result = String::with_capacity(url_template.len() + encoded_term.len());
-            result.push_str(url_template);
-            result.push_str(&encoded_term);
-            return result;
-        }
+/// Remove every whole word of the query that case-insensitively equals one of `words`.
+fn strip_words<'a>(query: &'a str, words: &[String]) -> Cow<'a, str> {
+    let is_noise = |word: &str| words.iter().any(|noise| noise.eq_ignore_ascii_case(word));
+    if !query.split(' ').any(is_noise) {
+        return Cow::Borrowed(query);
     }
+    Cow::Owned(
+        query
+            .split(' ')
+            .filter(|word| !is_noise(word))
+            .collect::<Vec<_>>()
+            .join(" "),
+    )
+}
 
-    // Default fallback
-    app_config
-        .default_search
-        .replace("{}", &urlencoding::encode(query))
+/// Extract and percent-decode the `name` parameter from a raw, still-encoded query string,
+/// falling back to `q` if there is none.
+///
+/// With `plus_as_space`, a literal `+` becomes a space before percent-decoding, so an encoded
+/// `%2B` still decodes to `+` rather than being converted twice.
+#[must_use]
+pub fn search_param(raw_query: &str, name: &str, plus_as_space: bool) -> Option<String> {
+    let find = |name: &str| {
+        raw_query
+            .split('&')
+            .find_map(|pair| match pair.split_once('=') {
+                Some((key, value)) if key == name => Some(value),
+                None if pair == name => Some(""),
+                _ => None,
+            })
+    };
+    let value = find(name).or_else(|| find("q"))?;
+    Some(decode_search_term(value, plus_as_space))
+}
+
+/// Percent-decode a search term taken from a URL, turning `+` into a space first with
+/// `plus_as_space`.
+#[must_use]
+pub fn decode_search_term(value: &str, plus_as_space: bool) -> String {
+    let value = if plus_as_space {
+        Cow::Owned(value.replace('+', " "))
+    } else {
+        Cow::Borrowed(value)
+    };
+    String::from_utf8_lossy(&urlencoding::decode_binary(value.as_bytes())).into_owned()
 }
 
+/// The template mode for a bang, honoring its `ignore_term` override over the global default.
+fn template_mode(app_config: &AppConfig, bang: &Bang) -> TemplateMode {
+    match bang.ignore_term {
+        Some(true) => TemplateMode::Verbatim,
+        Some(false) => TemplateMode::Append,
+        None => app_config.default_template_mode,
+    }
+}
+
+/// How the search term is put into a bang's URL template.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TermPlacement {
+    /// The term replaces each of the template's [`PLACEHOLDERS`].
+    Placeholder,
+    /// The template has no placeholder and the term is appended to it.
+    Append,
+    /// The template has no placeholder and is used as-is, dropping the term.
+    Verbatim,
+}
+
+/// Where the search term goes in a bang's template.
+///
+/// A POST bang without a placeholder sends the term in its body, so its URL is used verbatim.
+fn term_placement(app_config: &AppConfig, bang: &Bang) -> TermPlacement {
+    if PLACEHOLDERS
+        .iter()
+        .any(|placeholder| bang.url_template.contains(placeholder))
+    {
+        return TermPlacement::Placeholder;
+    }
+    if bang.method == Some(BangMethod::Post) {
+        return TermPlacement::Verbatim;
+    }
+    match template_mode(app_config, bang) {
+        TemplateMode::Append => TermPlacement::Append,
+        TemplateMode::Verbatim => TermPlacement::Verbatim,
+    }
+}
+
+/// Replace every one of the [`PLACEHOLDERS`] in `template` with `term`.
+///
+/// Done in a single pass so a placeholder appearing in `term` is never replaced itself.
+fn fill_placeholders(template: &str, term: &str) -> String {
+    let mut url = String::with_capacity(template.len() + term.len());
+    let mut rest = template;
+    while let Some((index, placeholder)) = PLACEHOLDERS
+        .iter()
+        .filter_map(|placeholder| rest.find(placeholder).map(|index| (index, placeholder)))
+        .min()
+    {
+        url.push_str(&rest[..index]);
+        url.push_str(term);
+        rest = &rest[index + placeholder.len()..];
+    }
+    url.push_str(rest);
+    url
+}
+
+/// The search term left after removing the bang, trimmed and with whitespace normalized if
+/// configured.
+fn search_term<'a>(app_config: &AppConfig, remainder: &'a str) -> Cow<'a, str> {
+    if app_config.normalize_whitespace {
+        normalize_whitespace(remainder)
+    } else {
+        Cow::Borrowed(remainder.trim())
+    }
+}
+
+/// The form fields a POST bang sends: its `body_template` with `{{{s}}}` replaced by the
+/// search term. The browser encodes them when submitting the form.
+fn post_fields(bang: &Bang, search_term: &str) -> Vec<(String, String)> {
+    let Some(body_template) = &bang.body_template else {
+        return Vec::new();
+    };
+    url::form_urlencoded::parse(body_template.as_bytes())
+        .map(|(name, value)| (name.into_owned(), value.replace("{{{s}}}", search_term)))
+        .collect()
+}
+
+/// Encode the search term left after removing the bang, honoring the whitespace, slash and
+/// space options of the configuration and the bang.
+fn encode_term(app_config: &AppConfig, bang: &Bang, remainder: &str) -> String {
+    let search_term = search_term(app_config, remainder);
+    let mut encoded_term = urlencoding::encode(&search_term);
+
+    // Fix slashes once in the encoded term
+    let decode_slashes = bang.decode_slashes.unwrap_or(app_config.decode_slashes);
+    if decode_slashes && encoded_term.contains("%2F") {
+        encoded_term = Cow::from(encoded_term.replace("%2F", "/"));
+    }
+    let space_as_plus = bang.space_as_plus.unwrap_or(app_config.space_as_plus);
+    if space_as_plus && encoded_term.contains("%20") {
+        encoded_term = Cow::from(encoded_term.replace("%20", "+"));
+    }
+    if bang.double_encode.unwrap_or(false) {
+        return urlencoding::encode(&encoded_term).into_owned();
+    }
+    encoded_term.into_owned()
+}
+
+/// Append an encoded search term to a template without a placeholder, keeping any fragment last.
+///
+/// Templates ending in `=`, `/` or `+` before the fragment take the term as-is; otherwise the
+/// term is joined to the query string with `?` or `&`.
+fn append_term(url_template: &str, encoded_term: &str) -> String {
+    let (base, fragment) = url_template
+        .split_once('#')
+        .map_or((url_template, None), |(base, fragment)| {
+            (base, Some(fragment))
+        });
+    if base.ends_with(['=', '/', '+']) {
+        let mut result = String::with_capacity(url_template.len() + encoded_term.len());
+        result.push_str(base);
+        result.push_str(encoded_term);
+        if let Some(fragment) = fragment {
+            result.push('#');
+            result.push_str(fragment);
+        }
+        return result;
+    }
+    if encoded_term.is_empty() {
+        return url_template.to_string();
+    }
+    let Ok(mut url) = Url::parse(url_template) else {
+        // Not a URL either way; the fallback chain deals with the result.
+        return format!("{url_template}{encoded_term}");
+    };
+    let query = match url.query() {
+        Some(query) if !query.is_empty() => format!("{query}&{encoded_term}"),
+        _ => encoded_term.to_string(),
+    };
+    url.set_query(Some(&query));
+    url.into()
+}
+
+#[cfg(feature = "server")]
 pub async fn periodic_update(app_config: AppConfig) {
-    let mut interval = interval(Duration::from_secs(24 * 60 * 60)); // 24 hours
+    let mut interval = tokio::time::interval(Duration::from_secs(24 * 60 * 60)); // 24 hours
     loop {
         interval.tick().await;
         if let Err(e) = update_bangs(&app_config).await {
@@ -136,59 +757,520 @@ pub async fn periodic_update(app_config: AppConfig) {
     }
 }
 
+/// Why [`update_bangs`] failed.
+#[derive(Debug)]
+pub enum UpdateError {
+    /// A bang list could not be downloaded.
+    #[cfg(feature = "network")]
+    Fetch(reqwest::Error),
+    /// A bang list is not valid JSON or not in a known bang list format.
+    Parse(serde_json::Error),
+    /// The bang list could not be written to or read from disk, or networking is disabled.
+    Io(std::io::Error),
+    /// The bang cache was left unchanged: no `bangs_url` is configured, or the new bang list
+    /// shrank too much and was rejected.
+    Cache,
+}
+
+impl Display for UpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            #[cfg(feature = "network")]
+            Self::Fetch(e) => write!(f, "failed to fetch the bang list: {e}"),
+            Self::Parse(e) => write!(f, "failed to parse the bang list: {e}"),
+            Self::Io(e) => write!(f, "I/O error: {e}"),
+            Self::Cache => write!(f, "the bang cache was not updated"),
+        }
+    }
+}
+
+impl std::error::Error for UpdateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "network")]
+            Self::Fetch(e) => Some(e),
+            Self::Parse(e) => Some(e),
+            Self::Io(e) => Some(e),
+            Self::Cache => None,
+        }
+    }
+}
+
+#[cfg(feature = "network")]
+impl From<reqwest::Error> for UpdateError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Fetch(e)
+    }
+}
+
+impl From<serde_json::Error> for UpdateError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Parse(e)
+    }
+}
+
+impl From<std::io::Error> for UpdateError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
 /// Update the bang cache with the latest bang commands.
 ///
+/// Bang lists given as a `file://` URL or an absolute path are read from disk, and the cached
+/// list is ignored once one of them changes.
+///
 /// # Errors
-/// If it fails to update the bang cache.
-pub async fn update_bangs(app_config: &AppConfig) -> anyhow::Result<()> {
-    let cache_path = std::env::temp_dir().join("bang_cache.json");
+/// If no bang list could be fetched and stored, or the cache rejected the new list.
+#[cfg(feature = "server")]
+pub async fn update_bangs(app_config: &AppConfig) -> Result<(), UpdateError> {
+    let result = update_bangs_in(&BANG_CACHE, app_config).await;
+    reindex_triggers();
+    result
+}
+
+/// [`update_bangs`] for the given bang cache instead of [`BANG_CACHE`].
+#[cfg(feature = "server")]
+async fn update_bangs_in(
+    cache: &RwLock<BangMap>,
+    app_config: &AppConfig,
+) -> Result<(), UpdateError> {
+    let cache_path = cache_path(app_config);
     let cache_age_limit = Duration::from_secs(24 * 60 * 60);
 
-    if let Ok(metadata) = std::fs::metadata(&cache_path) {
-        if let Ok(modified) = metadata.modified() {
-            if modified.elapsed()? < cache_age_limit {
-                if let Ok(contents) = std::fs::read_to_string(&cache_path) {
-                    let bang_entries: Vec<Bang> = serde_json::from_str(&contents)?;
-                    debug!("Bang cache is up to date.");
-                    update_cache(bang_entries, app_config);
-                    return Ok(());
-                }
+    if let Ok(metadata) = std::fs::metadata(&cache_path)
+        && let Ok(modified) = metadata.modified()
+        && modified.elapsed().is_ok_and(|age| age < cache_age_limit)
+        && !app_config
+            .bangs_url
+            .iter()
+            .filter_map(|url| local_bang_list(url))
+            .any(|path| {
+                std::fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .is_ok_and(|changed| changed > modified)
+            })
+        && load_cached_bangs_in(cache, app_config).is_ok()
+    {
+        debug!("Bang cache is up to date.");
+        return Ok(());
+    }
+
+    // Each list is streamed to disk and parsed from there, so the raw response and the parsed
+    // bangs are never held in memory at the same time.
+    let mut bang_entries = Vec::new();
+    let mut downloads = Vec::new();
+    let mut last_error = None;
+    for (index, url) in app_config.bangs_url.iter().enumerate() {
+        let download = cache_path.with_extension(format!("{index}.part"));
+        let fetched = match local_bang_list(url) {
+            Some(path) => std::fs::copy(path, &download)
+                .map(drop)
+                .map_err(UpdateError::from),
+            None => fetch_bang_list(url, &download).await,
+        }
+        .and_then(|()| read_bang_list(&download));
+        match fetched {
+            Ok(bangs) => {
+                bang_entries.extend(bangs);
+                downloads.push(download);
             }
+            Err(e) => {
+                warn!("Failed to fetch the bang list from '{}': {}", url, e);
+                let _ = std::fs::remove_file(&download);
+                last_error = Some(e);
+            }
+        }
+    }
+    if downloads.is_empty() {
+        return Err(last_error.unwrap_or(UpdateError::Cache));
+    }
+
+    // A single list is cached as served; merged lists are cached in the order they override.
+    let staged = if let [download] = downloads.as_slice() {
+        download.clone()
+    } else {
+        let staged = cache_path.with_extension("part");
+        let written = std::fs::File::create(&staged)
+            .map_err(UpdateError::from)
+            .and_then(|file| {
+                serde_json::to_writer(std::io::BufWriter::new(file), &bang_entries)
+                    .map_err(UpdateError::from)
+            });
+        for download in &downloads {
+            let _ = std::fs::remove_file(download);
+        }
+        if let Err(e) = written {
+            let _ = std::fs::remove_file(&staged);
+            return Err(e);
         }
+        staged
+    };
+    // Only a list the cache accepted replaces the one on disk, or a restart would load a
+    // rejected list without the shrink check.
+    if update_cache_in(cache, bang_entries, app_config) {
+        std::fs::rename(&staged, &cache_path)?;
+        Ok(())
+    } else {
+        let _ = std::fs::remove_file(&staged);
+        Err(UpdateError::Cache)
     }
+}
 
-    let response = reqwest::get(&app_config.bangs_url).await?.text().await?;
-    let bang_entries: Vec<Bang> = serde_json::from_str(&response)?;
+/// The file a `bangs_url` refers to if it is a `file://` URL or an absolute path, so the bang
+/// list is read from disk instead of being fetched.
+#[cfg(feature = "server")]
+fn local_bang_list(url: &str) -> Option<PathBuf> {
+    if url.starts_with("file:") {
+        return Url::parse(url).ok()?.to_file_path().ok();
+    }
+    let path = Path::new(url);
+    path.is_absolute().then(|| path.to_path_buf())
+}
 
-    std::fs::write(cache_path, &response)?;
-    update_cache(bang_entries, app_config);
+/// The HTTP client shared by every outgoing request, with [`FETCH_TIMEOUT`] applied.
+#[cfg(feature = "network")]
+pub fn http_client() -> &'static reqwest::Client {
+    static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+        reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .build()
+            .expect("Failed to build the HTTP client")
+    });
+    &CLIENT
+}
+
+/// Stream the raw bang list from `url` into the file at `dest`.
+///
+/// The list is requested compressed and written decompressed, so the on-disk cache holds
+/// plain JSON.
+#[cfg(feature = "network")]
+async fn fetch_bang_list(url: &str, dest: &Path) -> Result<(), UpdateError> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut response = http_client().get(url).send().await?;
+    let mut file = tokio::fs::File::create(dest).await?;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
     Ok(())
 }
 
-/// Update the bang cache with the provided bang commands.
+/// Without networking there is nothing to fetch from; bangs have to be supplied through
+/// [`update_cache`].
+#[cfg(all(feature = "server", not(feature = "network")))]
+async fn fetch_bang_list(_url: &str, _dest: &Path) -> Result<(), UpdateError> {
+    Err(UpdateError::Io(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "networking disabled: redirector was built without the `network` feature",
+    )))
+}
+
+/// Path of the bang list cached on disk.
 ///
-/// # Errors
-/// If it fails to get the write lock on the bang cache or the last update time.
-fn update_cache(bang_entries: Vec<Bang>, app_config: &AppConfig) {
-    let mut cache = BANG_CACHE.write();
-    cache.clear();
-    for bang in bang_entries {
-        cache.insert(bang.trigger.clone(), bang.url_template.clone());
+/// The configured `cache_dir` is created if missing. If it cannot be created or written to,
+/// the system temp directory is used instead. Each directory is only checked the first time,
+/// as this runs on every update.
+fn cache_path(app_config: &AppConfig) -> PathBuf {
+    const CACHE_FILE: &str = "bang_cache.json";
+    /// Whether each configured `cache_dir` turned out to be writable.
+    static WRITABLE_DIRS: LazyLock<Mutex<HashMap<PathBuf, bool>>> = LazyLock::new(Mutex::default);
+
+    if let Some(dir) = &app_config.cache_dir {
+        let writable = *WRITABLE_DIRS.lock().entry(dir.clone()).or_insert_with(|| {
+            let probe = dir.join(".write_test");
+            let writable = std::fs::create_dir_all(dir)
+                .and_then(|()| std::fs::write(&probe, []))
+                .and_then(|()| std::fs::remove_file(&probe));
+            if let Err(e) = &writable {
+                warn!(
+                    "Cache directory '{}' is not writable, using the temp directory instead: {}",
+                    dir.display(),
+                    e
+                );
+            }
+            writable.is_ok()
+        });
+        if writable {
+            return dir.join(CACHE_FILE);
+        }
     }
-    if let Some(bangs) = &app_config.bangs {
-        for bang in bangs {
-            cache.insert(bang.trigger.clone(), bang.url_template.clone());
+    std::env::temp_dir().join(CACHE_FILE)
+}
+
+/// Load the bang list cached on disk into the bang cache, regardless of its age, without any
+/// network request. Configured bangs are merged in as with a fetched list.
+///
+/// # Errors
+/// If the cached bang list is missing or cannot be read or parsed.
+pub fn load_cached_bangs(app_config: &AppConfig) -> anyhow::Result<()> {
+    load_cached_bangs_in(&BANG_CACHE, app_config)?;
+    reindex_triggers();
+    Ok(())
+}
+
+/// [`load_cached_bangs`] into the given bang cache instead of [`BANG_CACHE`].
+fn load_cached_bangs_in(cache: &RwLock<BangMap>, app_config: &AppConfig) -> anyhow::Result<()> {
+    let cache_path = cache_path(app_config);
+    let bang_entries = read_bang_list(&cache_path).map_err(|e| {
+        anyhow::anyhow!(
+            "cannot load the cached bang list at '{}': {e}",
+            cache_path.display()
+        )
+    })?;
+    update_cache_in(cache, bang_entries, app_config);
+    Ok(())
+}
+
+/// Parse a bang list file without reading it into memory first.
+fn read_bang_list(path: &Path) -> Result<Vec<Bang>, UpdateError> {
+    let file = std::fs::File::open(path)?;
+    let BangSource(bang_entries) = serde_json::from_reader(std::io::BufReader::new(file))?;
+    Ok(bang_entries)
+}
+
+/// Populate the bang cache before serving the first request.
+///
+/// Loads the built-in and configured bangs first, so they work even before the bang list is
+/// fetched. Falls back to the bang list cached on disk, and then to the built-in and configured
+/// bangs alone, when the bang source cannot be fetched.
+///
+/// # Errors
+/// If no bang list could be loaded and there are neither built-in nor configured bangs.
+#[cfg(feature = "server")]
+pub async fn initialize_bangs(app_config: &AppConfig) -> anyhow::Result<()> {
+    update_cache(Vec::new(), app_config);
+    let Err(fetch_error) = update_bangs(app_config).await else {
+        return Ok(());
+    };
+    warn!("Failed to update bang commands: {}", fetch_error);
+
+    match load_cached_bangs(app_config) {
+        Ok(()) => {
+            warn!("Using the bang commands cached on disk.");
+            Ok(())
+        }
+        Err(e) if is_ready() => {
+            warn!(
+                "Failed to load cached bang commands, only configured and built-in bangs are available: {}",
+                e
+            );
+            Ok(())
         }
+        Err(e) => Err(anyhow::anyhow!(
+            "no bang commands available: {fetch_error}; cached bang commands: {e}"
+        )),
+    }
+}
+
+/// Whether any bangs, built-in ones included, are loaded and requests can be served.
+#[must_use]
+pub fn is_ready() -> bool {
+    is_ready_in(&BANG_CACHE)
+}
+
+/// [`is_ready`] for the given bang cache instead of [`BANG_CACHE`].
+fn is_ready_in(cache: &RwLock<BangMap>) -> bool {
+    !cache.read().is_empty()
+}
+
+/// Update the bang cache with the provided bang commands, followed by the configured bangs.
+///
+/// This is how bangs are supplied when the crate is built without the `network` feature.
+/// Returns whether the cache was replaced, see `reject_shrunk_bangs`.
+pub fn update_cache(bang_entries: Vec<Bang>, app_config: &AppConfig) -> bool {
+    let replaced = update_cache_in(&BANG_CACHE, bang_entries, app_config);
+    reindex_triggers();
+    replaced
+}
+
+/// [`update_cache`] for the given bang cache instead of [`BANG_CACHE`].
+fn update_cache_in(
+    cache: &RwLock<BangMap>,
+    bang_entries: Vec<Bang>,
+    app_config: &AppConfig,
+) -> bool {
+    let (new_cache, overrides) = build_cache(bang_entries, app_config);
+    if !overrides.duplicates.is_empty() {
+        debug!(
+            triggers = ?overrides.duplicates,
+            "The bang list repeats triggers, the last bang for each was kept."
+        );
+    }
+    if !overrides.by_config.is_empty() {
+        debug!(
+            triggers = ?overrides.by_config,
+            "Configured bangs override bangs from the bang list."
+        );
+    }
+    info!(
+        "Loaded {} bangs, {} overridden by config.",
+        new_cache.len(),
+        overrides.by_config.len()
+    );
+    let mut cache = cache.write();
+    if !swap_cache(&mut cache, new_cache, app_config) {
+        return false;
     }
     drop(cache);
-    *LAST_UPDATE.write() = Instant::now();
+    #[cfg(feature = "server")]
+    {
+        *LAST_UPDATE.write() = Instant::now();
+    }
     debug!("Bang commands updated successfully.");
+    true
+}
+
+/// Triggers whose bang was replaced by a later one while building the cache.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Overrides {
+    /// Triggers listed more than once in the fetched bang list.
+    duplicates: Vec<String>,
+    /// Triggers from the bang list replaced by a configured bang.
+    by_config: Vec<String>,
+}
+
+/// Key remote bangs by trigger, with configured bangs overriding them.
+fn build_cache(bang_entries: Vec<Bang>, app_config: &AppConfig) -> (BangMap, Overrides) {
+    let mut cache = BangMap::with_capacity_and_hasher(bang_entries.len(), FxBuildHasher);
+    let mut overrides = Overrides::default();
+    if app_config.builtin_bangs {
+        cache.extend(BUILTIN_BANGS.iter().map(|bang| (bang.key(), bang.clone())));
+    }
+    for bang in bang_entries {
+        let bang = Bang {
+            source: BangSourceKind::Remote,
+            ..bang
+        };
+        if let Some(replaced) = cache.insert(bang.key(), bang)
+            && replaced.source == BangSourceKind::Remote
+        {
+            overrides.duplicates.push(replaced.trigger);
+        }
+    }
+    for bang in app_config.bangs.iter().flatten() {
+        if let Some(replaced) = cache.insert(bang.key(), bang.configured())
+            && replaced.source == BangSourceKind::Remote
+        {
+            overrides.by_config.push(bang.trigger.clone());
+        }
+    }
+    (cache, overrides)
+}
+
+/// Replace the cache with the new entries, unless they shrank by more than
+/// `max_bangs_shrink_percent` and `reject_shrunk_bangs` is set.
+///
+/// Returns whether the cache was replaced.
+fn swap_cache(cache: &mut BangMap, new_cache: BangMap, app_config: &AppConfig) -> bool {
+    if shrunk_too_much(
+        cache.len(),
+        new_cache.len(),
+        app_config.max_bangs_shrink_percent,
+    ) {
+        metrics::increment(&metrics::BANG_LIST_SHRINKS);
+        warn!(
+            "Bang list shrank from {} to {} entries.",
+            cache.len(),
+            new_cache.len()
+        );
+        if app_config.reject_shrunk_bangs {
+            metrics::increment(&metrics::BANG_UPDATES_REJECTED);
+            warn!("Keeping the previous bang commands.");
+            return false;
+        }
+    }
+    *cache = new_cache;
+    true
+}
+
+/// Whether going from `previous` to `new` entries is a drop of more than `max_shrink_percent`.
+const fn shrunk_too_much(previous: usize, new: usize, max_shrink_percent: u8) -> bool {
+    new < previous && (previous - new) * 100 > previous * max_shrink_percent as usize
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn test_update_bangs_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let write_list = |name: &str, trigger: &str| {
+            let path = dir.path().join(name);
+            let list = serde_json::json!([{ "t": trigger, "u": "https://example.com/?q={{{s}}}" }]);
+            std::fs::write(&path, list.to_string()).unwrap();
+            path
+        };
+        let url_list = write_list("url.json", "fileurltest");
+        let path_list = write_list("path.json", "filepathtest");
+        let app_config = AppConfig {
+            bangs_url: vec![
+                Url::from_file_path(&url_list).unwrap().to_string(),
+                path_list.to_string_lossy().into_owned(),
+            ],
+            cache_dir: Some(dir.path().join("cache")),
+            ..AppConfig::default()
+        };
+
+        let cache = RwLock::new(BangMap::default());
+        update_bangs_in(&cache, &app_config).await.unwrap();
+        let cache = cache.read();
+        assert!(cache.contains_key("fileurltest"));
+        assert!(cache.contains_key("filepathtest"));
+        // The merged list is cached in the configured directory, which is created for it.
+        let cached = read_bang_list(&dir.path().join("cache").join("bang_cache.json")).unwrap();
+        assert_eq!(cached.len(), 2);
+    }
+
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn test_update_bangs_keeps_cache_file_of_rejected_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let list = dir.path().join("bangs.json");
+        let write_list = |count: usize| {
+            let bangs: Vec<_> = (0..count)
+                .map(|i| serde_json::json!({ "t": format!("rejecttest{i}"), "u": "https://example.com/?q=" }))
+                .collect();
+            std::fs::write(&list, serde_json::to_string(&bangs).unwrap()).unwrap();
+        };
+        let app_config = AppConfig {
+            bangs_url: vec![list.to_string_lossy().into_owned()],
+            cache_dir: Some(dir.path().join("cache")),
+            builtin_bangs: false,
+            reject_shrunk_bangs: true,
+            ..AppConfig::default()
+        };
+        let cache = RwLock::new(BangMap::default());
+        let cache_file = cache_path(&app_config);
+
+        write_list(10);
+        update_bangs_in(&cache, &app_config).await.unwrap();
+        assert_eq!(read_bang_list(&cache_file).unwrap().len(), 10);
+        // Make the change to the list visible despite coarse file timestamps.
+        std::fs::File::options()
+            .write(true)
+            .open(&cache_file)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+
+        write_list(1);
+        let error = update_bangs_in(&cache, &app_config).await.unwrap_err();
+        assert!(matches!(error, UpdateError::Cache), "{error:?}");
+        assert_eq!(cache.read().len(), 10);
+        // The rejected list neither replaces the cached one nor is left behind.
+        assert_eq!(read_bang_list(&cache_file).unwrap().len(), 10);
+        assert_eq!(
+            std::fs::read_dir(cache_file.parent().unwrap())
+                .unwrap()
+                .count(),
+            1
+        );
+    }
+
     #[tokio::test]
     async fn test_get_bang() {
         // Valid bang queries
@@ -208,49 +1290,53 @@ mod tests {
         assert_eq!(get_bang("a!!gh"), None); // No space before !
     }
 
-    #[tokio::test]
-    async fn test_resolve_with_bang() {
+    /// The checked-in bang list the resolve tests run against, so they need no network access.
+    fn fixture_bangs() -> BangMap {
+        let BangSource(bangs) =
+            serde_json::from_str(include_str!("../tests/fixtures/bangs.json")).unwrap();
+        bangs.into_iter().map(|bang| (bang.key(), bang)).collect()
+    }
+
+    #[test]
+    fn test_resolve_with_bang() {
         let config = AppConfig::default();
-        if let Err(e) = update_bangs(&config).await {
-            error!("Failed to update bangs: {}", e);
-        };
+        let bangs = fixture_bangs();
+        let resolve = |query| resolve_with(&bangs, &config, query).url;
 
         // Test with template that has {{{s}}}
-        let result = resolve(&config, "!g rust programming");
+        let result = resolve("!g rust programming");
         assert_eq!(result, "https://www.google.com/search?q=rust%20programming");
 
         // Test with template that doesn't have {{{s}}}
-        let result = resolve(&config, "!gh rust programming");
+        let result = resolve("!gh rust programming");
         assert_eq!(
             result,
             "https://github.com/search?utf8=%E2%9C%93&q=rust%20programming"
         );
 
         // Test with bang at different position
-        let result = resolve(&config, "rust !yt programming");
+        let result = resolve("rust !yt programming");
         assert_eq!(
             result,
-            "https://www.youtube.com/results?search_query=rust%20%20programming"
+            "https://www.youtube.com/results?search_query=rust%20programming"
         );
     }
 
-    #[tokio::test]
-    async fn test_resolve_without_bang() {
+    #[test]
+    fn test_resolve_without_bang() {
         let config = AppConfig::default();
-
-        if let Err(e) = update_bangs(&config).await {
-            error!("Failed to update bangs: {}", e);
-        };
+        let bangs = fixture_bangs();
+        let resolve = |query| resolve_with(&bangs, &config, query).url;
 
         // Test with no bang
-        let result = resolve(&config, "rust programming");
+        let result = resolve("rust programming");
         assert_eq!(
             result,
             config.default_search.replace("{}", "rust%20programming")
         );
 
         // Test with non-matching bang
-        let result = resolve(&config, "!nonexistent rust programming");
+        let result = resolve("!nonexistent rust programming");
         assert_eq!(
             result,
             config
@@ -259,27 +1345,869 @@ mod tests {
         );
     }
 
-    #[tokio::test]
-    async fn test_resolve_edge_cases() {
+    #[test]
+    fn test_resolve_edge_cases() {
         let config = AppConfig::default();
-
-        if let Err(e) = update_bangs(&config).await {
-            error!("Failed to update bangs: {}", e);
-        };
+        let bangs = fixture_bangs();
+        let resolve = |query| resolve_with(&bangs, &config, query).url;
 
         // Empty query
-        let result = resolve(&config, "");
+        let result = resolve("");
         assert_eq!(result, config.default_search.replace("{}", ""));
 
         // URL encoding special chars
-        let result = resolve(&config, "!g c++ & rust/wasm");
+        let result = resolve("!g c++ & rust/wasm");
         assert_eq!(
             result,
             "https://www.google.com/search?q=c%2B%2B%20%26%20rust/wasm"
         );
 
         // Only a bang with no search term
-        let result = resolve(&config, "!g");
+        let result = resolve("!g");
         assert_eq!(result, "https://www.google.com/search?q=");
     }
+
+    #[cfg(feature = "network")]
+    #[tokio::test]
+    #[ignore = "fetches the live DuckDuckGo bang list"]
+    async fn test_resolve_live_bang_list() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let config = AppConfig {
+            cache_dir: Some(cache_dir.path().to_path_buf()),
+            ..AppConfig::default()
+        };
+        update_bangs(&config).await.unwrap();
+
+        let result = resolve(&config, "!g rust programming");
+        assert_eq!(result, "https://www.google.com/search?q=rust%20programming");
+    }
+
+    #[test]
+    fn test_placeholderless_template_modes() {
+        let bang = Bang::new("ex", "https://example.com/search?q=");
+        let mut config = AppConfig::default();
+        assert_eq!(term_placement(&config, &bang), TermPlacement::Append);
+
+        config.default_template_mode = TemplateMode::Verbatim;
+        assert_eq!(term_placement(&config, &bang), TermPlacement::Verbatim);
+
+        let bang = Bang::new("ex", "https://example.com/search?q={{{s}}}");
+        assert_eq!(term_placement(&config, &bang), TermPlacement::Placeholder);
+    }
+
+    #[test]
+    fn test_remove_bang() {
+        let remove = |query: &str| remove_bang(query, get_bang(query).unwrap());
+        assert_eq!(remove("rust !yt programming"), "rust programming");
+        assert_eq!(remove("!yt rust"), " rust");
+        assert_eq!(remove("rust !yt"), "rust");
+        // Only the gap left by the bang is collapsed.
+        assert_eq!(remove("rust  lang !yt book"), "rust  lang book");
+        // The bang found by `get_bang` is removed, not an earlier lookalike.
+        assert_eq!(remove("a!yt !yt b"), "a!yt b");
+    }
+
+    #[test]
+    fn test_normalize_whitespace() {
+        assert_eq!(normalize_whitespace("rust   lang"), "rust lang");
+        assert_eq!(
+            normalize_whitespace("rust\t\tlang\t book"),
+            "rust lang book"
+        );
+        assert_eq!(normalize_whitespace("  rust lang  "), "rust lang");
+        assert_eq!(normalize_whitespace(" \t "), "");
+        assert!(matches!(
+            normalize_whitespace("rust lang"),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_resolve_normalizes_whitespace() {
+        let cache = BangMap::from_iter([(
+            "g".to_string(),
+            Bang::new("g", "https://www.google.com/search?q={{{s}}}"),
+        )]);
+        let mut config = AppConfig::default();
+
+        let resolve = |config: &AppConfig, query| resolve_with(&cache, config, query).url;
+        assert_eq!(
+            resolve(&config, "!g   rust \t lang  "),
+            "https://www.google.com/search?q=rust%20lang"
+        );
+        assert_eq!(resolve(&config, "!g"), "https://www.google.com/search?q=");
+        assert_eq!(
+            resolve(&config, "rust   lang"),
+            "https://www.qwant.com/?q=rust%20lang"
+        );
+
+        config.normalize_whitespace = false;
+        assert_eq!(
+            resolve(&config, "!g   rust \t lang  "),
+            "https://www.google.com/search?q=rust%20%09%20lang"
+        );
+    }
+
+    #[test]
+    fn test_resolve_placeholder_forms() {
+        let cache = BangMap::from_iter([
+            (
+                "braces".to_string(),
+                Bang::new("braces", "https://example.com/?q={}"),
+            ),
+            (
+                "printf".to_string(),
+                Bang::new("printf", "https://example.com/%s/?q=%s"),
+            ),
+        ]);
+        let config = AppConfig::default();
+
+        let resolve = |query| resolve_with(&cache, &config, query).url;
+        assert_eq!(resolve("!braces rust"), "https://example.com/?q=rust");
+        assert_eq!(
+            resolve("!printf rust lang"),
+            "https://example.com/rust%20lang/?q=rust%20lang"
+        );
+        assert_eq!(
+            fill_placeholders("a={}&b=%s", "{{{s}}}"),
+            "a={{{s}}}&b={{{s}}}"
+        );
+    }
+
+    #[test]
+    fn test_resolve_decode_slashes() {
+        let mut path_style = Bang::new("gh", "https://github.com/{{{s}}}");
+        path_style.decode_slashes = Some(true);
+        let cache = BangMap::from_iter([
+            (
+                "g".to_string(),
+                Bang::new("g", "https://www.google.com/search?q={{{s}}}"),
+            ),
+            ("gh".to_string(), path_style),
+        ]);
+        let mut config = AppConfig::default();
+
+        let resolve = |config: &AppConfig, query| resolve_with(&cache, config, query).url;
+        assert_eq!(
+            resolve(&config, "!g a/b"),
+            "https://www.google.com/search?q=a/b"
+        );
+
+        config.decode_slashes = false;
+        assert_eq!(
+            resolve(&config, "!g a/b"),
+            "https://www.google.com/search?q=a%2Fb"
+        );
+        assert_eq!(
+            resolve(&config, "!gh rust-lang/rust"),
+            "https://github.com/rust-lang/rust"
+        );
+    }
+
+    #[test]
+    fn test_resolve_space_as_plus() {
+        let mut legacy = Bang::new("old", "https://example.com/search.cgi?query=");
+        legacy.space_as_plus = Some(true);
+        let cache = BangMap::from_iter([
+            ("old".to_string(), legacy),
+            (
+                "g".to_string(),
+                Bang::new("g", "https://www.google.com/search?q={{{s}}}"),
+            ),
+        ]);
+        let config = AppConfig::default();
+
+        let resolve = |query| resolve_with(&cache, &config, query).url;
+        assert_eq!(
+            resolve("!old rust lang"),
+            "https://example.com/search.cgi?query=rust+lang"
+        );
+        // A literal plus is still encoded, so it stays distinguishable from a space.
+        assert_eq!(
+            resolve("!old c++ lang"),
+            "https://example.com/search.cgi?query=c%2B%2B+lang"
+        );
+        assert_eq!(
+            resolve("!g rust lang"),
+            "https://www.google.com/search?q=rust%20lang"
+        );
+    }
+
+    #[test]
+    fn test_explain() {
+        let cache = BangMap::from_iter([(
+            "gh".to_string(),
+            Bang::new("gh", "https://github.com/search?q=").configured(),
+        )]);
+        let config = AppConfig {
+            strip_words: Some(vec!["please".to_string()]),
+            ..AppConfig::default()
+        };
+
+        let explanation = explain_in(&cache, &config, "please !gh foo bar");
+        assert_eq!(explanation.query, "!gh foo bar");
+        assert_eq!(
+            explanation.outcome.url,
+            "https://github.com/search?q=foo%20bar"
+        );
+        assert_eq!(explanation.outcome.trigger(), Some("gh"));
+        assert_eq!(
+            explanation.template.as_deref(),
+            Some("https://github.com/search?q=")
+        );
+        assert_eq!(explanation.encoded_term.as_deref(), Some("foo%20bar"));
+        assert_eq!(explanation.placement, Some(TermPlacement::Append));
+
+        let explanation = explain_in(&cache, &config, "!nope foo");
+        assert_eq!(explanation.outcome.kind, MatchKind::DefaultBang);
+        assert_eq!(explanation.template, None);
+        assert_eq!(explanation.placement, None);
+    }
+
+    #[test]
+    fn test_cache_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("nested").join("cache");
+        let config = AppConfig {
+            cache_dir: Some(cache_dir.clone()),
+            ..AppConfig::default()
+        };
+        let path = cache_path(&config);
+        assert_eq!(path, cache_dir.join("bang_cache.json"));
+        std::fs::write(&path, "[]").unwrap();
+        assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), 1);
+
+        // A directory that cannot be created falls back to the temp directory.
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        let config = AppConfig {
+            cache_dir: Some(file.join("cache")),
+            ..AppConfig::default()
+        };
+        assert_eq!(
+            cache_path(&config),
+            std::env::temp_dir().join("bang_cache.json")
+        );
+        assert_eq!(
+            cache_path(&AppConfig::default()),
+            std::env::temp_dir().join("bang_cache.json")
+        );
+    }
+
+    #[test]
+    fn test_append_term_keeps_fragment_and_query() {
+        assert_eq!(
+            append_term("https://x.com/s?lang=en#top", "rust%20lang"),
+            "https://x.com/s?lang=en&rust%20lang#top"
+        );
+        assert_eq!(
+            append_term("https://x.com/s?q=#top", "rust"),
+            "https://x.com/s?q=rust#top"
+        );
+        assert_eq!(
+            append_term("https://x.com/wiki/#content", "Rust"),
+            "https://x.com/wiki/Rust#content"
+        );
+        assert_eq!(
+            append_term("https://x.com/search", "rust"),
+            "https://x.com/search?rust"
+        );
+        assert_eq!(
+            append_term("https://x.com/s?lang=en#top", ""),
+            "https://x.com/s?lang=en#top"
+        );
+    }
+
+    #[test]
+    fn test_template_mode_override() {
+        let config = AppConfig {
+            default_template_mode: TemplateMode::Verbatim,
+            ..AppConfig::default()
+        };
+        let mut bang = Bang::new("home", "https://example.com/");
+        assert_eq!(template_mode(&config, &bang), TemplateMode::Verbatim);
+
+        bang.ignore_term = Some(false);
+        assert_eq!(template_mode(&config, &bang), TemplateMode::Append);
+    }
+
+    #[test]
+    fn test_shrunk_too_much() {
+        assert!(!shrunk_too_much(0, 0, 50));
+        assert!(!shrunk_too_much(100, 120, 50));
+        assert!(!shrunk_too_much(100, 50, 50));
+        assert!(shrunk_too_much(100, 49, 50));
+        assert!(shrunk_too_much(100, 0, 0));
+    }
+
+    #[test]
+    fn test_swap_cache_rejects_shrunk_list() {
+        let mut config = AppConfig {
+            max_bangs_shrink_percent: 50,
+            ..AppConfig::default()
+        };
+        let previous: BangMap = (0..10)
+            .map(|i| {
+                let trigger = format!("bang{i}");
+                let bang = Bang::new(trigger.clone(), format!("https://example.com/{i}?q="));
+                (trigger, bang)
+            })
+            .collect();
+        let shrunk: BangMap = previous.clone().into_iter().take(2).collect();
+
+        // Shrinking is only warned about by default.
+        let mut cache = previous.clone();
+        assert!(swap_cache(&mut cache, shrunk.clone(), &config));
+        assert_eq!(cache.len(), 2);
+
+        // With rejection enabled the previous cache is kept.
+        config.reject_shrunk_bangs = true;
+        let mut cache = previous.clone();
+        assert!(!swap_cache(&mut cache, shrunk, &config));
+        assert_eq!(cache, previous);
+
+        // A list within the limit is still accepted.
+        let smaller: BangMap = previous.clone().into_iter().take(6).collect();
+        assert!(swap_cache(&mut cache, smaller, &config));
+        assert_eq!(cache.len(), 6);
+    }
+
+    #[test]
+    fn test_double_encode() {
+        let config = AppConfig::default();
+        let mut bang = Bang::new(
+            "out",
+            "https://out.example/?to=https%3A%2F%2Fsearch.example%2F%3Fq%3D{{{s}}}",
+        );
+        let mut cache = BangMap::default();
+        cache.insert(bang.key(), bang.clone());
+        assert_eq!(
+            resolve_with(&cache, &config, "!out a&b c").url,
+            "https://out.example/?to=https%3A%2F%2Fsearch.example%2F%3Fq%3Da%26b%20c"
+        );
+
+        bang.double_encode = Some(true);
+        cache.insert(bang.key(), bang);
+        assert_eq!(
+            resolve_with(&cache, &config, "!out a&b c").url,
+            "https://out.example/?to=https%3A%2F%2Fsearch.example%2F%3Fq%3Da%2526b%2520c"
+        );
+    }
+
+    #[test]
+    fn test_wildcard_bang() {
+        let mut cache = BangMap::default();
+        let wildcard = Bang::new(
+            WILDCARD_TRIGGER,
+            "https://{t}.wiki.example/search?q={{{s}}}",
+        );
+        assert_eq!(wildcard.validate(), Ok(()));
+        cache.insert(wildcard.key(), wildcard);
+        let config = AppConfig::default();
+
+        let outcome = resolve_with(&cache, &config, "!De rust programming");
+        assert_eq!(
+            outcome.url,
+            "https://de.wiki.example/search?q=rust%20programming"
+        );
+        assert_eq!(outcome.trigger(), Some(WILDCARD_TRIGGER));
+        // Explaining it shows the template with the typed trigger filled in.
+        assert_eq!(
+            explain_in(&cache, &config, "!De rust").template.as_deref(),
+            Some("https://de.wiki.example/search?q={{{s}}}")
+        );
+
+        // An exact trigger takes priority over the wildcard.
+        let exact = Bang::new("en", "https://english.example/?q={{{s}}}");
+        cache.insert(exact.key(), exact);
+        let outcome = resolve_with(&cache, &config, "!en rust");
+        assert_eq!(outcome.url, "https://english.example/?q=rust");
+        assert_eq!(outcome.trigger(), Some("en"));
+
+        // Queries without a bang still go to the default search.
+        assert_eq!(
+            resolve_with(&cache, &config, "rust programming").kind,
+            MatchKind::DefaultSearch
+        );
+        assert_eq!(
+            bang_suggestions_in(&cache, &index_triggers(&cache), "!"),
+            Some(vec!["!en".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_bang_suggestions_shortest_first() {
+        let triggers = ["ab", "a", "b", "abc", "aa"]
+            .map(str::to_string)
+            .into_iter()
+            .chain((2..12).map(|len| format!("a{}", "z".repeat(len))));
+        let mut cache: BangMap = triggers
+            .map(|trigger| {
+                let bang = Bang::new(trigger.to_uppercase(), "https://example.com/?q=");
+                (trigger, bang)
+            })
+            .collect();
+        let index = index_triggers(&cache);
+        let suggestions = bang_suggestions_in(&cache, &index, "rust !a").unwrap();
+        assert_eq!(suggestions.len(), MAX_BANG_SUGGESTIONS);
+        assert_eq!(
+            suggestions[..5],
+            ["rust !A", "rust !AA", "rust !AB", "rust !ABC", "rust !AZZ"]
+        );
+
+        // Triggers gone from the cache since it was indexed are not suggested.
+        cache.remove("aa");
+        assert_eq!(
+            bang_suggestions_in(&cache, &index, "!a").unwrap()[..2],
+            ["!A", "!AB"]
+        );
+        assert_eq!(bang_suggestions_in(&cache, &index, "!a "), None);
+    }
+
+    #[test]
+    fn test_strip_unknown_bang() {
+        let cache = BangMap::default();
+        let config = AppConfig::default();
+        assert_eq!(
+            resolve_with(&cache, &config, "rust !xyz programming").url,
+            "https://www.qwant.com/?q=rust%20%21xyz%20programming"
+        );
+
+        let config = AppConfig {
+            strip_unknown_bang: true,
+            ..AppConfig::default()
+        };
+        let outcome = resolve_with(&cache, &config, "rust !xyz programming");
+        assert_eq!(outcome.url, "https://www.qwant.com/?q=rust%20programming");
+        assert_eq!(outcome.kind, MatchKind::DefaultBang);
+        assert_eq!(
+            resolve_with(&cache, &config, "!xyz rust").url,
+            "https://www.qwant.com/?q=rust"
+        );
+        // Only the bang is removed, a `!` inside a word is part of the search.
+        assert_eq!(
+            resolve_with(&cache, &config, "hello! world").url,
+            "https://www.qwant.com/?q=hello%21%20world"
+        );
+    }
+
+    #[test]
+    fn test_search_param() {
+        assert_eq!(search_param("q=a+b", "q", true).as_deref(), Some("a b"));
+        assert_eq!(search_param("q=a%2Bb", "q", true).as_deref(), Some("a+b"));
+        assert_eq!(search_param("q=a+b", "q", false).as_deref(), Some("a+b"));
+        assert_eq!(
+            search_param("theme=dark&q=!g+c%2B%2B", "q", true).as_deref(),
+            Some("!g c++")
+        );
+        assert_eq!(search_param("q", "q", true).as_deref(), Some(""));
+        assert_eq!(search_param("query=rust", "q", true), None);
+        assert_eq!(
+            search_param("query=rust", "query", true).as_deref(),
+            Some("rust")
+        );
+        assert_eq!(
+            search_param("q=go&query=rust", "query", true).as_deref(),
+            Some("rust")
+        );
+        assert_eq!(search_param("q=go", "query", true).as_deref(), Some("go"));
+    }
+
+    #[test]
+    fn test_strip_words() {
+        let words = vec!["NOISE".to_string(), "0b7f3c2e".to_string()];
+
+        assert_eq!(strip_words("rust programming", &words), "rust programming");
+        assert!(matches!(
+            strip_words("rust programming", &words),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(
+            strip_words("!g rust noise programming 0B7F3C2E", &words),
+            "!g rust programming"
+        );
+        // Only whole words are removed.
+        assert_eq!(strip_words("noisemaker", &words), "noisemaker");
+
+        let config = AppConfig {
+            strip_words: Some(words),
+            ..AppConfig::default()
+        };
+        assert_eq!(
+            resolve(&config, "rust noise"),
+            config.default_search.replace("{}", "rust")
+        );
+    }
+
+    #[test]
+    fn test_resolve_match_kind() {
+        let config = AppConfig::default();
+        let cache = BangMap::from_iter([(
+            "ex".to_string(),
+            Bang::new("ex", "https://example.com/?q={{{s}}}"),
+        )]);
+
+        let outcome = resolve_with(&cache, &config, "!EX rust");
+        assert_eq!(outcome.url, "https://example.com/?q=rust");
+        assert_eq!(
+            outcome.kind,
+            MatchKind::BangMatched {
+                trigger: "ex".to_string()
+            }
+        );
+        assert_eq!(outcome.trigger(), Some("ex"));
+
+        let outcome = resolve_with(&cache, &config, "rust programming");
+        assert_eq!(outcome.kind, MatchKind::DefaultSearch);
+        assert_eq!(outcome.trigger(), None);
+
+        let outcome = resolve_with(&cache, &config, "!unknown rust");
+        assert_eq!(outcome.kind, MatchKind::DefaultBang);
+        assert_eq!(
+            outcome.url,
+            config.default_search.replace("{}", "%21unknown%20rust")
+        );
+
+        // The thin wrapper only keeps the URL.
+        assert_eq!(
+            resolve(&config, "rust"),
+            resolve_detailed(&config, "rust").url
+        );
+    }
+
+    #[test]
+    fn test_resolve_fallback_chain() {
+        let cache = BangMap::from_iter([(
+            "broken".to_string(),
+            Bang::new("broken", "example.com/?q={{{s}}}"),
+        )]);
+        let mut config = AppConfig::default();
+
+        // A malformed bang template falls back to the default search, without the bang.
+        let outcome = resolve_with(&cache, &config, "!broken rust");
+        assert_eq!(outcome.kind, MatchKind::DefaultBang);
+        assert_eq!(outcome.url, config.default_search.replace("{}", "rust"));
+        let outcome = resolve_with(&cache, &config, "rust  !broken lang");
+        assert_eq!(
+            outcome.url,
+            config.default_search.replace("{}", "rust%20lang")
+        );
+
+        // With a malformed default search too, the embedded search is used.
+        config.default_search = "not a url {}".to_string();
+        let outcome = resolve_with(&cache, &config, "!broken rust");
+        assert_eq!(outcome.kind, MatchKind::EmbeddedFallback);
+        assert_eq!(outcome.url, "https://duckduckgo.com/?q=rust");
+        // Plain queries go through the same chain.
+        assert_eq!(
+            resolve_with(&cache, &config, "rust").kind,
+            MatchKind::EmbeddedFallback
+        );
+
+        // The chain stops at the error page, even with steps left after it.
+        config.resolve_fallback_chain = vec![FallbackStep::ErrorPage, FallbackStep::Embedded];
+        let outcome = resolve_with(&cache, &config, "!broken rust");
+        assert_eq!(outcome.kind, MatchKind::Unresolved);
+        assert!(outcome.url.is_empty());
+
+        // An exhausted chain is unresolved as well.
+        config.resolve_fallback_chain = vec![FallbackStep::DefaultSearch];
+        assert_eq!(
+            resolve_with(&cache, &config, "!broken rust").kind,
+            MatchKind::Unresolved
+        );
+    }
+
+    #[test]
+    fn test_is_redirectable() {
+        assert!(is_redirectable("https://example.com/?q=rust"));
+        assert!(is_redirectable("HTTP://example.com"));
+        assert!(!is_redirectable("example.com/?q=rust"));
+        assert!(!is_redirectable("https://"));
+        assert!(!is_redirectable("https:///path"));
+        assert!(!is_redirectable("javascript:alert(1)"));
+        assert!(!is_redirectable("https://example.com/\r\nSet-Cookie: a=b"));
+    }
+
+    #[test]
+    fn test_resolve_source() {
+        let config = AppConfig {
+            bangs: Some(vec![Bang::new("g", "https://example.com/config?q={{{s}}}")]),
+            ..AppConfig::default()
+        };
+        let (cache, overrides) = build_cache(
+            vec![
+                Bang::new("g", "https://example.com/remote?q={{{s}}}"),
+                Bang::new("remoteonly", "https://example.com/remote?q={{{s}}}"),
+                Bang::new("remoteonly", "https://example.com/remote?q={{{s}}}"),
+            ],
+            &config,
+        );
+        assert_eq!(
+            overrides,
+            Overrides {
+                duplicates: vec!["remoteonly".to_string()],
+                by_config: vec!["g".to_string()],
+            }
+        );
+
+        let outcome = resolve_with(&cache, &config, "!g rust");
+        assert_eq!(outcome.url, "https://example.com/config?q=rust");
+        assert_eq!(outcome.source, BangSourceKind::Config);
+        let outcome = resolve_with(&cache, &config, "!remoteonly rust");
+        assert_eq!(outcome.source, BangSourceKind::Remote);
+        let outcome = resolve_with(&cache, &config, "!unknown rust");
+        assert_eq!(outcome.source, BangSourceKind::Default);
+        assert_eq!(serde_json::to_value(&outcome).unwrap()["source"], "default");
+    }
+
+    #[test]
+    fn test_builtin_bangs() {
+        let mut config = AppConfig::default();
+        let (cache, _) = build_cache(Vec::new(), &config);
+        for trigger in ["g", "w", "yt", "gh", "so", "maps"] {
+            assert_eq!(cache[trigger].source, BangSourceKind::Builtin);
+            assert_eq!(cache[trigger].validate(), Ok(()));
+        }
+        let outcome = resolve_with(&cache, &config, "!so rust");
+        assert_eq!(outcome.url, "https://stackoverflow.com/search?q=rust");
+        assert_eq!(outcome.source, BangSourceKind::Builtin);
+        // The built-in bangs alone are enough to serve requests.
+        assert!(is_ready_in(&RwLock::new(cache)));
+
+        // Fetched bangs override built-in ones without being reported as duplicates.
+        let (cache, overrides) = build_cache(
+            vec![Bang::new("so", "https://remote.example/?q={{{s}}}")],
+            &config,
+        );
+        assert_eq!(overrides, Overrides::default());
+        let outcome = resolve_with(&cache, &config, "!so rust");
+        assert_eq!(outcome.url, "https://remote.example/?q=rust");
+        assert_eq!(outcome.source, BangSourceKind::Remote);
+
+        config.builtin_bangs = false;
+        let (cache, _) = build_cache(Vec::new(), &config);
+        assert!(cache.is_empty());
+        assert!(!is_ready_in(&RwLock::new(cache)));
+    }
+
+    #[test]
+    fn test_bang_sources_precedence() {
+        use crate::config::FileConfig;
+
+        let BangSource(first) = serde_json::from_str(
+            r#"[{"t": "shared", "u": "https://first.example/?q={{{s}}}"},
+                {"t": "firstonly", "u": "https://first.example/?q={{{s}}}"},
+                {"t": "configured", "u": "https://first.example/?q={{{s}}}"}]"#,
+        )
+        .unwrap();
+        let BangSource(second) = serde_json::from_str(
+            r#"{"bangs": [{"t": "shared", "u": "https://second.example/?q={{{s}}}"}]}"#,
+        )
+        .unwrap();
+        let config = AppConfig {
+            bangs: Some(vec![Bang::new(
+                "configured",
+                "https://config.example/?q={{{s}}}",
+            )]),
+            ..AppConfig::default()
+        };
+        let (cache, _) = build_cache(first.into_iter().chain(second).collect(), &config);
+
+        let url = |query| resolve_with(&cache, &config, query).url;
+        assert_eq!(url("!shared a"), "https://second.example/?q=a");
+        assert_eq!(url("!firstonly a"), "https://first.example/?q=a");
+        assert_eq!(url("!configured a"), "https://config.example/?q=a");
+
+        // `bangs_url` takes a single URL or a list of them.
+        let file: FileConfig = toml::from_str(r#"bangs_url = "https://a.example""#).unwrap();
+        assert_eq!(file.bangs_url.unwrap(), ["https://a.example"]);
+        let file: FileConfig =
+            toml::from_str(r#"bangs_url = ["https://a.example", "https://b.example"]"#).unwrap();
+        assert_eq!(
+            file.bangs_url.unwrap(),
+            ["https://a.example", "https://b.example"]
+        );
+        let file: FileConfig = toml::from_str("").unwrap();
+        assert!(file.bangs_url.is_none());
+    }
+
+    #[test]
+    fn test_default_search_by_lang() {
+        let config = AppConfig {
+            default_search_by_lang: HashMap::from([
+                (
+                    "de".to_string(),
+                    "https://search.example.de/?q={}".to_string(),
+                ),
+                (
+                    "de-CH".to_string(),
+                    "https://search.example.ch/?q={}".to_string(),
+                ),
+            ]),
+            ..AppConfig::default()
+        };
+        assert_eq!(
+            config.default_search_for(Some("de-AT")),
+            "https://search.example.de/?q={}"
+        );
+        assert_eq!(
+            config.default_search_for(Some("DE-ch")),
+            "https://search.example.ch/?q={}"
+        );
+        // Only whole subtags match, and unknown or missing languages use `default_search`.
+        assert_eq!(
+            config.default_search_for(Some("dev")),
+            config.default_search
+        );
+        assert_eq!(
+            config.default_search_for(Some("fr-FR")),
+            config.default_search
+        );
+        assert_eq!(config.default_search_for(None), config.default_search);
+
+        let cache = BangMap::default();
+        let de = config.default_search_for(Some("de-AT"));
+        assert_eq!(
+            resolve_in(&cache, &config, de, "rust lang").url,
+            "https://search.example.de/?q=rust%20lang"
+        );
+        let fr = config.default_search_for(Some("fr"));
+        assert_eq!(
+            resolve_in(&cache, &config, fr, "rust lang").url,
+            "https://www.qwant.com/?q=rust%20lang"
+        );
+    }
+
+    #[test]
+    fn test_resolve_with_std_hashmap() {
+        let cache = HashMap::from([(
+            "tenant".to_string(),
+            Bang::new("tenant", "https://tenant.example/?q={{{s}}}"),
+        )]);
+        let config = AppConfig::default();
+
+        let outcome = resolve_with(&cache, &config, "!tenant rust");
+        assert_eq!(outcome.url, "https://tenant.example/?q=rust");
+        assert_eq!(outcome.trigger(), Some("tenant"));
+        assert_eq!(
+            resolve_with(&HashMap::new(), &config, "!tenant rust").kind,
+            MatchKind::DefaultBang
+        );
+    }
+
+    #[test]
+    fn test_mixed_case_triggers() {
+        let config = AppConfig {
+            bangs: Some(vec![Bang::new("MyGH", "https://config.example/?q={{{s}}}")]),
+            ..AppConfig::default()
+        };
+        let (cache, _) = build_cache(
+            vec![Bang::new("DDG", "https://remote.example/?q={{{s}}}")],
+            &config,
+        );
+
+        for query in ["!mygh rust", "!MyGH rust", "!MYGH rust"] {
+            assert_eq!(
+                resolve_with(&cache, &config, query).url,
+                "https://config.example/?q=rust"
+            );
+        }
+        assert_eq!(
+            resolve_with(&cache, &config, "!ddg rust").url,
+            "https://remote.example/?q=rust"
+        );
+    }
+
+    #[cfg(feature = "network")]
+    #[tokio::test]
+    async fn test_fetch_bang_list_decompresses() {
+        use axum::http::HeaderMap;
+        use axum::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING};
+        use flate2::{Compression, write::GzEncoder};
+        use std::io::Write;
+
+        const BANGS: &str = r#"[{"t": "gz", "u": "https://example.com/?q="}]"#;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(BANGS.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().route(
+            "/bang.js",
+            axum::routing::get(move |headers: HeaderMap| async move {
+                let accept_encoding = headers.get(ACCEPT_ENCODING).unwrap().to_str().unwrap();
+                assert!(accept_encoding.contains("gzip"), "{accept_encoding}");
+                ([(CONTENT_ENCODING, "gzip")], compressed)
+            }),
+        );
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("bang.js");
+        fetch_bang_list(&format!("http://{addr}/bang.js"), &dest)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), BANGS);
+        assert_eq!(read_bang_list(&dest).unwrap()[0].trigger, "gz");
+    }
+
+    #[cfg(feature = "network")]
+    #[tokio::test]
+    async fn test_update_bangs_error_variants() {
+        let dir = tempfile::tempdir().unwrap();
+        // Nothing listens on a port once its listener is dropped.
+        let dead_addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let config = AppConfig {
+            cache_dir: Some(dir.path().to_path_buf()),
+            bangs_url: vec![format!("http://{dead_addr}/bang.js")],
+            ..AppConfig::default()
+        };
+        let error = update_bangs(&config).await.unwrap_err();
+        assert!(matches!(error, UpdateError::Fetch(_)), "{error:?}");
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app =
+            axum::Router::new().route("/bang.js", axum::routing::get(|| async { "{not json" }));
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        let config = AppConfig {
+            bangs_url: vec![format!("http://{addr}/bang.js")],
+            ..config
+        };
+        let error = update_bangs(&config).await.unwrap_err();
+        assert!(matches!(error, UpdateError::Parse(_)), "{error:?}");
+        // The partial download of the malformed list is cleaned up.
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[cfg(all(feature = "server", not(feature = "network")))]
+    #[tokio::test]
+    async fn test_update_bangs_without_network() {
+        let config = AppConfig {
+            bangs_url: vec!["https://example.com/bang.js".to_string()],
+            ..AppConfig::default()
+        };
+        let error = fetch_bang_list(&config.bangs_url[0], Path::new("unused"))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("networking disabled"));
+
+        update_cache(
+            vec![Bang::new("offline", "https://example.com/?q=")],
+            &config,
+        );
+        assert_eq!(
+            resolve(&config, "!offline rust"),
+            "https://example.com/?q=rust"
+        );
+    }
 }
@@ -1,174 +1,271 @@
-use axum::extract::State;
-use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
-use axum::response::{Html, IntoResponse};
-use axum::routing::post;
-use axum::{Json, Router, extract::Query, response::Redirect, routing::get};
+use axum::Router;
 use clap::{CommandFactory, Parser};
 use clap_complete::generate;
-use heck::ToTitleCase;
+use redirector::bang::{BangSourceKind, WILDCARD_TRIGGER};
 use redirector::cli::SubCommand::Completions;
 use redirector::cli::{Cli, SubCommand};
-use redirector::config::{AppState, append_file_config, get_file_config};
-use redirector::{BANG_CACHE, periodic_update, resolve, update_bangs};
-use reqwest::Client;
-use serde::Deserialize;
+use redirector::config::{
+    AppConfig, AppState, Config, config_path, get_file_config, rewrite_file_bangs,
+};
+use redirector::prune::partition_dead_bangs;
+use redirector::server::build_router;
+use redirector::{
+    BANG_CACHE, BangMap, Explanation, MatchKind, TermPlacement, get_bang, http_client,
+    initialize_bangs, load_cached_bangs, periodic_update, resolve_detailed, resolve_with,
+    update_bangs, update_cache,
+};
 use std::fmt::Write;
-use std::{env, net::SocketAddr, time::Instant};
+use std::io::{self, BufRead};
+use std::net::SocketAddr;
+use std::path::Path;
 use tokio::net::TcpListener;
-use tracing::{Level, debug, error, info};
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tracing::{Level, error, info, warn};
 
-#[derive(Debug, Deserialize)]
-struct SearchParams {
-    #[serde(rename = "q")]
-    query: Option<String>,
-}
+/// Resolves once the process is asked to stop with Ctrl+C or, on Unix, `SIGTERM`.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-/// Handler function that extracts the `q` parameter and redirects accordingly
-async fn handler(
-    Query(params): Query<SearchParams>,
-    State(app_state): State<AppState>,
-) -> Redirect {
-    params.query.map_or_else(
-        || Redirect::to("/bangs"),
-        |query| {
-            let start = Instant::now();
-            let redirect_url = resolve(&app_state.get_config(), &query);
-            debug!("Request completed in {:?}", start.elapsed());
-            info!("Redirecting '{}' to '{}'.", query, redirect_url);
-            Redirect::to(&redirect_url)
-        },
-    )
+    tokio::select! {
+        () = ctrl_c => {},
+        () = terminate => {},
+    }
+    info!("Shutting down.");
 }
 
-async fn list_bangs(State(app_state): State<AppState>) -> Html<String> {
-    let pkg_name = env!("CARGO_PKG_NAME").to_title_case();
-    let mut html = String::from(
-        "<style>:root { background: #181818; color: #ffffff; font-family: monospace; } table { border-collapse: collapse; width: 100vw; } table th { text-align: left; padding: 1rem 0; font-size: 1.25rem; width: 100vw; } table tr { border-bottom: #ffffff10 solid 2px; } table tr:nth-child(2n) { background: #161616; } table tr:nth-child(2n+1) { background: #181818; }</style><html>",
-    );
-    html += format!(r#"<head><meta charset="UTF-8"><meta name="viewport" content="width=device-width, initial-scale=1.0"><link rel="search" type="application/opensearchdescription+xml" title="{pkg_name}" href="/opensearch.xml"/><title>Bang Commands</title></head><body><h1>Bang Commands</h1>"#).as_str();
-
-    if let Some(bangs) = &app_state.get_config().bangs {
-        html.push_str("<h2>Configured Bangs</h2><table><th>Abbr.</th><th>Trigger</th><th>URL</th>");
-        for bang in bangs {
-            write!(
-                html,
-                "<tr><td><strong>{:?}</strong></td><td>{}</td><td>{}</td></tr>",
-                bang.short_name, bang.trigger, bang.url_template
-            )
-            .expect("Failed to write to HTML string");
+/// Bind every configured address and record the addresses actually bound, which differ from the
+/// configured ones for port `0`, in the app state and in `addr_file`.
+///
+/// Returns `None` after logging why if an address cannot be bound.
+async fn bind_listeners(app_state: &AppState) -> Option<Vec<TcpListener>> {
+    let (addrs, addr_file) = {
+        let config = app_state.read_config();
+        (config.socket_addrs(), config.addr_file.clone())
+    };
+    let mut listeners = Vec::new();
+    let mut bound = Vec::new();
+    for addr in addrs {
+        match TcpListener::bind(addr).await {
+            Ok(listener) => {
+                let addr = listener.local_addr().unwrap_or(addr);
+                info!("Server running on '{}'", addr);
+                listeners.push(listener);
+                bound.push(addr);
+            }
+            Err(e) => {
+                match bind_error_hint(addr, &e) {
+                    Some(hint) => error!("Failed to bind to address '{}': {}. {}", addr, e, hint),
+                    None => error!("Failed to bind to address '{}': {}", addr, e),
+                }
+                return None;
+            }
         }
-        html.push_str("</table>");
     }
+    if let Some(path) = addr_file {
+        let contents: String = bound.iter().map(|addr| format!("{addr}\n")).collect();
+        if let Err(e) = std::fs::write(&path, contents) {
+            warn!(
+                "Failed to write the listening addresses to '{}': {}",
+                path.display(),
+                e
+            );
+        }
+    }
+    *app_state.bound_addrs.write() = bound;
+    Some(listeners)
+}
 
-    html.push_str("<h2>Active Bangs</h2><table><th>Trigger</th><th>URL</th>");
-    for (trigger, url_template) in BANG_CACHE.read().iter() {
-        write!(
-            html,
-            "<tr><td><strong>{trigger}</strong></td><td>{url_template}</td></tr>"
-        )
-        .expect("Failed to write to HTML string");
+/// What to do about the common reasons binding to `addr` fails, if `error` is one of them.
+fn bind_error_hint(addr: SocketAddr, error: &io::Error) -> Option<String> {
+    match error.kind() {
+        io::ErrorKind::AddrInUse => Some(format!(
+            "Another process is already listening on port {}, stop it or choose another port with --port",
+            addr.port()
+        )),
+        io::ErrorKind::PermissionDenied if addr.port() < 1024 => Some(format!(
+            "Port {} requires elevated privileges or CAP_NET_BIND_SERVICE, or choose a port above 1023",
+            addr.port()
+        )),
+        io::ErrorKind::AddrNotAvailable => Some(format!(
+            "{} is not an address of this machine, use 0.0.0.0 to listen on all interfaces or 127.0.0.1 for local connections only",
+            addr.ip()
+        )),
+        _ => None,
     }
-    html.push_str("</ul></body></html>");
-    Html(html)
 }
 
-async fn opensearch(State(app_state): State<AppState>) -> impl IntoResponse {
-    let pkg_name = env!("CARGO_PKG_NAME");
-    let pkg_description = env!("CARGO_PKG_DESCRIPTION");
-    let app_config = app_state.get_config();
-    let opensearch_xml = format!(
-        r#"<?xml version="1.0" encoding="UTF-8"?>
-<OpenSearchDescription
-  xmlns="http://a9.com/-/spec/opensearch/1.1/"
-  xmlns:moz="http://www.mozilla.org/2006/browser/search/">
-  <ShortName>{}</ShortName>
-  <Description>{}</Description>
-  <InputEncoding>UTF-8</InputEncoding>
-  <Image height="64" width="64">data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAEAAAABACAYAAACqaXHeAAAACXBIWXMAADsOAAA7DgHMtqGDAAAAGXRFWHRTb2Z0d2FyZQB3d3cuaW5rc2NhcGUub3Jnm+48GgAABA9JREFUeJztm8trVVcUxn831WAoqB2pra9kVCWKOC21tA4sIoivkZFSHxEUpDP9AzootEXpRBpfYAoFHwMFHThRYlBBdKBodaBttEnsJPFRjd5SPwfrRBNzc88+r72veD8I5N691jprfdlnr7XX3ilJ4n1GQ2gHQqNOQGgHQqNOQGgHQqNOQGgHQiMkAZ8AR4EzwJcZbU0CPgd2AieA+8C/wO44xVKgQqgFuApMiT4PAouBvxLY+AhYAawEvgY+jL4XUIp+fzziGZUhKcTPIY3FAUfdBkk/SxqqYONt/BlnL0TwsyWVKzhbljTTQX+1Q+CS9FLS8Th7IdaAdmBihe8nAlsd9C8Bz4CXMXIl4GKcMd8ENAKbq4xvjmSqoQ8jsRQjBzVIwBpgWpXx6cAqBzsXgBcxMk+Ay3GGfBOw3UFmm4PMj8CEGJnTQDnOkE8CFgKfOcgtAVqrjLcAy4gn4KSLUz4JaE8guyVmbOQ68T823cWbhfEZcMrpSZ5SX5OkQcf0JUkDkU4lW72RzAtJTyQdkTRL0npJT6OxH1x980XANwmCH0bbOLbuSron6XtJzW+NzZC0UlYsOfnmqxTuxu39H4ku4IsCfBkFHwTMB26k0FOkeytfd0bDxyL4bUq9ErAxT0cqPqTgGdAI9GAFTho8AObgkM/ToqgZMB/4iWzBE+n2YIXPvBz8GoM8Z0ATsA7L90kXPFd0A/uwRspQHgbzIGABFnQbMDWzR254CPwGdADXsxhKS0Aj1olpB5bitjMrClcwIjpJMSuSEjAb27NvovquLgT+AfYDv2I9QTc4VkwtsjZWpU5OraEs6aDGVompK8GvgOP4e7/zwiMsG/VVE3JJgzt594IH6wbviRNyIeBRdl+CYXKcgMsr8DFwk7j+eu1hAFgLnK0m5DID+oBFwAHgv+x+FY4y5utiYoKH5GlwJpb7t5CtxC0C/Vga7AD+dlVKWwh9ACwHdlAbhdAvwO+kmKF5lMKt2IzYgJ3X+cAgVvl1kK7X8Bp5boYmYYtOO3ZSWwS6sM3QMeB5HgaL6gd8ijUz2oAZGW31YxufgxTQHar1hkg/MJd3sCEyjDL210uLTgoMHvw0RedhhVRSKNK9na87o+GjKfoH1slJii4KDh78HY3t86STGL4ORpqwktp1VzmAXaLKJdVVg68ZMESyxbATD8GD31tiC4BrjrKtZKzwXOHzePw6cN5B7hyeggf/N0T25iSTG3xflGwE7jF+R7kfOwrz1nfwPQPK2J59POzHc9MlxFXZWcAdxt4VLGP3f3p9OhPiouR9KqfEw3gOHsJdlm7GOjnDDZSHWN+xx7cjoQgAuyK/Cwv+O2wB9I6QBNQE6v8xEtqB0KgTENqB0KgTENqB0HjvCXgFiecDVd5zzR0AAAAASUVORK5CYII=</Image>
-  <Url type="text/html" method="GET" template="http://{}:{}/?q={{searchTerms}}" />
-  <Url type="application/x-suggestions+json" method="GET" template="http://{}:{}/suggest?q={{searchTerms}}" />
-</OpenSearchDescription>"#,
-        pkg_name.to_title_case(),
-        pkg_description,
-        app_config.ip,
-        app_config.port,
-        app_config.ip,
-        app_config.port
-    );
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        header::CONTENT_TYPE,
-        HeaderValue::from_static("application/opensearchdescription+xml"),
-    );
-    (StatusCode::OK, headers, opensearch_xml)
+/// Serve the router on every listener concurrently until `shutdown` resolves.
+async fn serve_tcp(
+    listeners: Vec<TcpListener>,
+    app: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) {
+    let (stop, stopped) = tokio::sync::watch::channel(());
+    let servers: Vec<_> = listeners
+        .into_iter()
+        .map(|listener| {
+            let mut stopped = stopped.clone();
+            let app = app
+                .clone()
+                .into_make_service_with_connect_info::<SocketAddr>();
+            let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+                stopped.changed().await.ok();
+            });
+            tokio::spawn(server.into_future())
+        })
+        .collect();
+    shutdown.await;
+    stop.send(()).ok();
+    for server in servers {
+        match server.await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Server error: {}", e),
+            Err(e) => error!("Server task failed: {}", e),
+        }
+    }
 }
 
-async fn suggestions_proxy(
-    Query(params): Query<SearchParams>,
-    State(app_state): State<AppState>,
-) -> impl IntoResponse {
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        header::CONTENT_TYPE,
-        HeaderValue::from_static("application/json"),
-    );
-
-    if let Some(query) = params.query {
-        let suggest_api_url = app_state
-            .get_config()
-            .search_suggestions
-            .replace("{}", &query);
-
-        match Client::new().get(&suggest_api_url).send().await {
-            Ok(response) => {
-                if let Ok(json) = response.json::<serde_json::Value>().await {
-                    return (StatusCode::OK, headers, Json(json));
-                }
-            }
-            Err(e) => {
-                error!("Failed to fetch suggestions from Brave API: {}", e);
-            }
+/// Serve the router on a Unix domain socket until `shutdown` resolves, then remove the socket.
+///
+/// A socket file left behind by a previous run is removed first, unless a server still accepts
+/// connections on it. Anything else at `path` is left alone and the server does not start.
+///
+/// # Errors
+/// If the socket cannot be set up or the server fails.
+#[cfg(unix)]
+async fn serve_unix(
+    path: &Path,
+    app: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    use anyhow::{Context, bail};
+    use std::os::unix::fs::FileTypeExt;
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            bail!(
+                "refusing to replace '{}': it exists and is not a socket",
+                path.display()
+            );
+        }
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            bail!(
+                "another server is already listening on '{}'",
+                path.display()
+            );
         }
+        std::fs::remove_file(path)
+            .with_context(|| format!("failed to remove stale socket '{}'", path.display()))?;
+    }
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("failed to bind to socket '{}'", path.display()))?;
+    info!("Server running on '{}'", path.display());
+    let served = axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown)
+        .await
+        .context("server error");
+    if let Err(e) = std::fs::remove_file(path) {
+        warn!("Failed to remove socket '{}': {}", path.display(), e);
     }
+    served
+}
 
-    (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        headers,
-        Json(serde_json::json!([])),
+#[cfg(not(unix))]
+async fn serve_unix(
+    path: &Path,
+    _app: Router,
+    _shutdown: impl Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    anyhow::bail!(
+        "cannot serve on '{}': Unix sockets are not supported on this platform",
+        path.display()
     )
 }
 
-// endpoint to add a new bang to the config file
-async fn add_bang(
-    Query(params): Query<redirector::bang::Bang>,
-    State(app_state): State<AppState>,
-) -> impl IntoResponse {
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        header::CONTENT_TYPE,
-        HeaderValue::from_static("application/json"),
-    );
-
-    let mut config = app_state.config.write();
-    if let Some(bangs) = &mut config.bangs {
-        append_file_config(params.clone());
-        bangs.push(params.clone());
-        if let Some(mut cache) = BANG_CACHE.try_write() {
-            cache.insert(params.trigger, params.url_template);
-        }
-        return (
-            StatusCode::OK,
-            headers,
-            Json(serde_json::json!({ "status": "success" })),
-        );
+/// Resolve each line of `input` as a query against `cache`, writing `query<TAB>url` lines to
+/// `output`.
+///
+/// Output is flushed after every line so results stream for large inputs. Blank lines resolve
+/// like an empty query.
+fn resolve_lines(
+    cache: &BangMap,
+    app_config: &AppConfig,
+    input: impl BufRead,
+    mut output: impl io::Write,
+) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        let query = line.strip_suffix('\r').unwrap_or(&line);
+        let url = resolve_with(cache, app_config, query).url;
+        writeln!(output, "{query}\t{url}")?;
+        output.flush()?;
     }
-    drop(config);
+    Ok(())
+}
 
-    (
-        StatusCode::BAD_REQUEST,
-        headers,
-        Json(serde_json::json!({ "status": "failed" })),
-    )
+/// Render an explanation from `resolve --explain` as aligned plain text.
+fn format_explanation(explanation: &Explanation) -> String {
+    let outcome = &explanation.outcome;
+    let matched = match &outcome.kind {
+        MatchKind::BangMatched { trigger } => {
+            let source = match outcome.source {
+                BangSourceKind::Config => "the configuration",
+                BangSourceKind::Builtin => "the built-in bangs",
+                BangSourceKind::Remote | BangSourceKind::Default => "the remote bang list",
+            };
+            // Name the trigger that was typed rather than the wildcard it matched.
+            match get_bang(&explanation.query) {
+                Some(typed) if trigger == WILDCARD_TRIGGER => {
+                    format!("bang {typed} through the !{trigger} wildcard from {source}")
+                }
+                _ => format!("bang !{trigger} from {source}"),
+            }
+        }
+        MatchKind::DefaultSearch => "no bang, default search".to_string(),
+        MatchKind::DefaultBang => "unknown bang, default search".to_string(),
+        MatchKind::EmbeddedFallback => "malformed URL, embedded fallback search".to_string(),
+        MatchKind::Unresolved => "malformed URL, no fallback succeeded".to_string(),
+    };
+
+    let mut text = String::new();
+    writeln!(text, "query:        {}", explanation.query).unwrap();
+    writeln!(text, "url:          {}", outcome.url).unwrap();
+    writeln!(text, "matched:      {matched}").unwrap();
+    if let Some(template) = &explanation.template {
+        writeln!(text, "template:     {template}").unwrap();
+    }
+    if let Some(encoded_term) = &explanation.encoded_term {
+        writeln!(text, "encoded term: {encoded_term}").unwrap();
+    }
+    if let Some(placement) = explanation.placement {
+        let placement = match placement {
+            TermPlacement::Placeholder => "replaced the {{{s}}} placeholder",
+            TermPlacement::Append => "appended to the template",
+            TermPlacement::Verbatim => "dropped, the template is used verbatim",
+        };
+        writeln!(text, "term:         {placement}").unwrap();
+    }
+    text
 }
 
 #[tokio::main]
@@ -185,41 +282,142 @@ async fn main() {
         .with_writer(std::io::stderr)
         .init();
 
-    let file_config = get_file_config();
+    let file_config = get_file_config(&config_path());
+    let port_set = matches!(
+        &cli_config.command,
+        Some(SubCommand::Serve { port: Some(_), .. })
+    ) || file_config
+        .as_ref()
+        .is_some_and(|file| file.port.is_some() || file.listen_addrs.is_some());
 
-    let app_config = file_config
-        .unwrap_or_default()
-        .merge(cli_config.clone().into());
+    let env_config = match Config::from_env() {
+        Ok(env_config) => env_config,
+        Err(e) => {
+            error!("Refusing to start: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let port_set = port_set || env_config.port.is_some();
+
+    let file_config = file_config.unwrap_or_default();
+    let mut problems = file_config.validate();
+    let app_config = file_config.merge(Config::from(cli_config.clone()).or(env_config));
+
+    problems.extend(app_config.validate());
+    for problem in &problems {
+        if cli_config.strict_config {
+            error!("Invalid configuration: {}", problem);
+        } else {
+            warn!("Invalid configuration: {}", problem);
+        }
+    }
+    if cli_config.strict_config && !problems.is_empty() {
+        error!("Refusing to start with an invalid configuration.");
+        std::process::exit(1);
+    }
 
     let app_state = AppState::new(app_config.clone());
 
     match cli_config.command {
         Some(SubCommand::Serve { .. }) | None => {
+            if app_config.unix_socket.is_some() && port_set {
+                error!(
+                    "Both a Unix socket and a TCP address are configured, set only one of them."
+                );
+                std::process::exit(1);
+            }
+            if let Err(e) = initialize_bangs(&app_config).await {
+                error!("Refusing to start: {}", e);
+                std::process::exit(1);
+            }
             tokio::spawn(periodic_update(app_config.clone()));
+            if app_config.admin_token.is_none() {
+                warn!(
+                    "No admin_token is configured, anyone who can reach the server can add bangs and reload the configuration."
+                );
+            }
 
-            let app = Router::new()
-                .route("/", get(handler))
-                .route("/bangs", get(list_bangs))
-                .route("/opensearch.xml", get(opensearch))
-                .route("/suggest", get(suggestions_proxy))
-                .route("/add_bang", post(add_bang))
-                .with_state(app_state);
-            let addr = SocketAddr::new(app_config.ip, app_config.port);
-            let listener = match TcpListener::bind(addr).await {
-                Ok(listener) => listener,
-                Err(e) => {
-                    error!("Failed to bind to address '{}': {}", addr, e);
-                    return;
+            let app = build_router(app_state.clone());
+            if let Some(path) = &app_config.unix_socket {
+                if let Err(e) = serve_unix(path, app, shutdown_signal()).await {
+                    error!("Cannot serve on the Unix socket: {:#}", e);
+                    std::process::exit(1);
                 }
+                return;
+            }
+            let Some(listeners) = bind_listeners(&app_state).await else {
+                std::process::exit(1);
             };
-            info!("Server running on '{}'", addr);
-            axum::serve(listener, app).await.unwrap();
+            serve_tcp(listeners, app, shutdown_signal()).await;
         }
-        Some(SubCommand::Resolve { query }) => {
-            if let Err(e) = update_bangs(&app_config).await {
+        Some(SubCommand::Resolve {
+            query,
+            explain,
+            json,
+            stdin,
+            offline,
+        }) => {
+            // The built-in and configured bangs work even if no bang list can be loaded.
+            update_cache(Vec::new(), &app_config);
+            if offline {
+                if let Err(e) = load_cached_bangs(&app_config) {
+                    error!("Cannot resolve offline: {}", e);
+                    std::process::exit(1);
+                }
+            } else if let Err(e) = update_bangs(&app_config).await {
                 error!("Failed to update bang commands: {}", e);
             }
-            println!("{}", resolve(&app_config, &query));
+            let query = query.join(" ");
+            if stdin || query == "-" {
+                let cache = BANG_CACHE.read();
+                if let Err(e) =
+                    resolve_lines(&cache, &app_config, io::stdin().lock(), io::stdout().lock())
+                {
+                    error!("Failed to resolve queries from stdin: {}", e);
+                }
+            } else if explain {
+                let explanation = redirector::explain(&app_config, &query);
+                if json {
+                    match serde_json::to_string_pretty(&explanation) {
+                        Ok(json) => println!("{json}"),
+                        Err(e) => error!("Failed to serialize the explanation: {}", e),
+                    }
+                } else {
+                    print!("{}", format_explanation(&explanation));
+                }
+            } else {
+                let outcome = resolve_detailed(&app_config, &query);
+                if outcome.kind == MatchKind::Unresolved {
+                    error!("The query resolved to a malformed URL and no fallback succeeded.");
+                    std::process::exit(1);
+                } else {
+                    println!("{}", outcome.url);
+                }
+            }
+        }
+        Some(SubCommand::Prune { apply, .. }) => {
+            let bangs = app_config.bangs.unwrap_or_default();
+            if bangs.is_empty() {
+                info!("No configured bangs to check.");
+                return;
+            }
+            let results = partition_dead_bangs(http_client(), bangs).await;
+            for bang in &results.dead {
+                println!("{}\t{}", bang.trigger, bang.url_template);
+            }
+            info!(
+                "{} of {} configured bangs are unreachable.",
+                results.dead.len(),
+                results.kept.len() + results.dead.len()
+            );
+            if apply && !results.dead.is_empty() {
+                if results.responded == 0 {
+                    // Most likely this machine is offline rather than every service gone.
+                    error!("No configured bang responded, refusing to remove them all.");
+                    std::process::exit(1);
+                }
+                rewrite_file_bangs(&app_state.config_path, &results.kept);
+            }
         }
         Some(Completions { shell }) => {
             generate(
@@ -231,3 +429,242 @@ async fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use redirector::bang::Bang;
+    use std::net::IpAddr;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_serve_unix_socket() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("redirector.sock");
+        // A leftover from a previous run that nothing listens on.
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let app = Router::new().route("/healthz", get(|| async { "ok" }));
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn({
+            let path = path.clone();
+            async move {
+                serve_unix(&path, app, async {
+                    stopped.await.ok();
+                })
+                .await
+                .unwrap();
+            }
+        });
+
+        let mut stream = loop {
+            if let Ok(stream) = tokio::net::UnixStream::connect(&path).await {
+                break stream;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        };
+        stream
+            .write_all(b"GET /healthz HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("ok"));
+
+        stop.send(()).unwrap();
+        server.await.unwrap();
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_serve_unix_keeps_regular_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("redirector.sock");
+        std::fs::write(&path, "data").unwrap();
+
+        // Fails straight away instead of serving.
+        let error = serve_unix(&path, Router::new(), std::future::pending())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("is not a socket"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+    }
+
+    #[tokio::test]
+    async fn test_bind_ephemeral_port() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr_file = dir.path().join("addr");
+        let app_state = AppState::new(AppConfig {
+            ip: IpAddr::from([127, 0, 0, 1]),
+            port: 0,
+            allow_ephemeral_port: true,
+            addr_file: Some(addr_file.clone()),
+            ..AppConfig::default()
+        });
+
+        let listeners = bind_listeners(&app_state).await.unwrap();
+        let addr = listeners[0].local_addr().unwrap();
+        assert_ne!(addr.port(), 0);
+        assert_eq!(*app_state.bound_addrs.read(), [addr]);
+        assert_eq!(
+            std::fs::read_to_string(&addr_file).unwrap(),
+            format!("{addr}\n")
+        );
+        let response = build_router(app_state)
+            .oneshot(
+                axum::extract::Request::get("/version")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["listening"], serde_json::json!([addr.to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_bind_error_hint() {
+        let taken = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = taken.local_addr().unwrap();
+        let error = TcpListener::bind(addr).await.unwrap_err();
+        let hint = bind_error_hint(addr, &error).unwrap();
+        assert!(hint.contains(&format!("port {}", addr.port())), "{hint}");
+
+        let hint = bind_error_hint(
+            SocketAddr::from(([0, 0, 0, 0], 80)),
+            &io::ErrorKind::PermissionDenied.into(),
+        )
+        .unwrap();
+        assert!(hint.contains("CAP_NET_BIND_SERVICE"), "{hint}");
+        let hint = bind_error_hint(
+            SocketAddr::from(([192, 0, 2, 1], 3000)),
+            &io::ErrorKind::AddrNotAvailable.into(),
+        )
+        .unwrap();
+        assert!(hint.starts_with("192.0.2.1 is not an address"), "{hint}");
+        assert_eq!(
+            bind_error_hint(
+                SocketAddr::from(([0, 0, 0, 0], 3000)),
+                &io::ErrorKind::PermissionDenied.into()
+            ),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serve_tcp_multiple_listeners() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let app = Router::new().route("/healthz", get(|| async { "ok" }));
+        let mut listeners = Vec::new();
+        let mut addrs = Vec::new();
+        for _ in 0..2 {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            addrs.push(listener.local_addr().unwrap());
+            listeners.push(listener);
+        }
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve_tcp(listeners, app, async {
+            stopped.await.ok();
+        }));
+
+        for addr in addrs {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            stream
+                .write_all(b"GET /healthz HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            assert!(response.starts_with("HTTP/1.1 200 OK"));
+            assert!(response.ends_with("ok"));
+        }
+
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[test]
+    fn test_resolve_lines() {
+        let cache = BangMap::from_iter([(
+            "stdinbang".to_string(),
+            Bang::new("stdinbang", "https://example.com/?q={{{s}}}"),
+        )]);
+        let config = AppConfig::default();
+
+        let input = "!stdinbang rust\r\n\nrust lang\n";
+        let mut output = Vec::new();
+        resolve_lines(&cache, &config, input.as_bytes(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "!stdinbang rust\thttps://example.com/?q=rust\n\
+             \thttps://www.qwant.com/?q=\n\
+             rust lang\thttps://www.qwant.com/?q=rust%20lang\n"
+        );
+    }
+
+    #[test]
+    fn test_format_explanation() {
+        let explanation = Explanation {
+            query: "!gh foo".to_string(),
+            outcome: redirector::ResolveOutcome {
+                url: "https://github.com/search?q=foo".to_string(),
+                kind: MatchKind::BangMatched {
+                    trigger: "gh".to_string(),
+                },
+                source: BangSourceKind::Config,
+                no_log: false,
+                form: None,
+                confirm: false,
+                status: None,
+            },
+            template: Some("https://github.com/search?q={{{s}}}".to_string()),
+            encoded_term: Some("foo".to_string()),
+            placement: Some(TermPlacement::Placeholder),
+        };
+        assert_eq!(
+            format_explanation(&explanation),
+            "query:        !gh foo\n\
+             url:          https://github.com/search?q=foo\n\
+             matched:      bang !gh from the configuration\n\
+             template:     https://github.com/search?q={{{s}}}\n\
+             encoded term: foo\n\
+             term:         replaced the {{{s}}} placeholder\n"
+        );
+    }
+
+    #[test]
+    fn test_format_explanation_wildcard() {
+        let explanation = Explanation {
+            query: "!Docs foo".to_string(),
+            outcome: redirector::ResolveOutcome {
+                url: "https://docs.example.com/search?q=foo".to_string(),
+                kind: MatchKind::BangMatched {
+                    trigger: WILDCARD_TRIGGER.to_string(),
+                },
+                source: BangSourceKind::Config,
+                no_log: false,
+                form: None,
+                confirm: false,
+                status: None,
+            },
+            template: Some("https://docs.example.com/search?q={{{s}}}".to_string()),
+            encoded_term: Some("foo".to_string()),
+            placement: Some(TermPlacement::Placeholder),
+        };
+        let text = format_explanation(&explanation);
+        assert!(
+            text.contains(
+                "matched:      bang !Docs through the !* wildcard from the configuration\n"
+            )
+        );
+    }
+}
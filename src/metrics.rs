@@ -0,0 +1,125 @@
+//! Process-wide counters, rendered in the Prometheus text format at `/metrics`.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Number of bang list updates that shrank by more than `max_bangs_shrink_percent`.
+pub static BANG_LIST_SHRINKS: AtomicU64 = AtomicU64::new(0);
+/// Number of bang list updates that were rejected because they shrank too much.
+pub static BANG_UPDATES_REJECTED: AtomicU64 = AtomicU64::new(0);
+/// Number of requests to `/suggest`.
+pub static SUGGESTION_REQUESTS: AtomicU64 = AtomicU64::new(0);
+/// Number of suggestion fetches that failed or returned an oversized or malformed response.
+pub static SUGGESTION_UPSTREAM_ERRORS: AtomicU64 = AtomicU64::new(0);
+/// How long fetching suggestions from `search_suggestions` took.
+pub static SUGGESTION_LATENCY: Histogram = Histogram::new();
+
+/// All counters with their name and help text.
+const COUNTERS: [(&str, &str, &AtomicU64); 4] = [
+    (
+        "redirector_bang_list_shrinks_total",
+        "Bang list updates that shrank by more than the configured percentage.",
+        &BANG_LIST_SHRINKS,
+    ),
+    (
+        "redirector_bang_updates_rejected_total",
+        "Bang list updates that were rejected because they shrank too much.",
+        &BANG_UPDATES_REJECTED,
+    ),
+    (
+        "redirector_suggestion_requests_total",
+        "Requests for search suggestions.",
+        &SUGGESTION_REQUESTS,
+    ),
+    (
+        "redirector_suggestion_upstream_errors_total",
+        "Suggestion fetches that failed or returned an oversized or malformed response.",
+        &SUGGESTION_UPSTREAM_ERRORS,
+    ),
+];
+
+/// All histograms with their name and help text.
+const HISTOGRAMS: [(&str, &str, &Histogram); 1] = [(
+    "redirector_suggestion_upstream_duration_seconds",
+    "Time taken to fetch search suggestions.",
+    &SUGGESTION_LATENCY,
+)];
+
+/// Upper bounds of the histogram buckets, in seconds.
+const BUCKETS: [f64; 8] = [0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// A histogram of durations with the fixed [`BUCKETS`].
+pub struct Histogram {
+    /// Observations at or below each bound, so the counts are already cumulative.
+    buckets: [AtomicU64; BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; BUCKETS.len()],
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// Record one observation.
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bound, bucket) in BUCKETS.iter().zip(&self.buckets) {
+            if seconds <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Increment a counter by one.
+#[inline]
+pub fn increment(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Render all counters in the Prometheus text exposition format.
+#[must_use]
+pub fn render() -> String {
+    let mut output = String::new();
+    for (name, help, counter) in COUNTERS {
+        writeln!(output, "# HELP {name} {help}").expect("Failed to write metrics");
+        writeln!(output, "# TYPE {name} counter").expect("Failed to write metrics");
+        writeln!(output, "{name} {}", counter.load(Ordering::Relaxed))
+            .expect("Failed to write metrics");
+    }
+    for (name, help, histogram) in HISTOGRAMS {
+        writeln!(output, "# HELP {name} {help}").expect("Failed to write metrics");
+        writeln!(output, "# TYPE {name} histogram").expect("Failed to write metrics");
+        for (bound, bucket) in BUCKETS.iter().zip(&histogram.buckets) {
+            writeln!(
+                output,
+                "{name}_bucket{{le=\"{bound}\"}} {}",
+                bucket.load(Ordering::Relaxed)
+            )
+            .expect("Failed to write metrics");
+        }
+        let count = histogram.count.load(Ordering::Relaxed);
+        #[allow(clippy::cast_precision_loss)]
+        let sum = histogram.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        writeln!(output, "{name}_bucket{{le=\"+Inf\"}} {count}").expect("Failed to write metrics");
+        writeln!(output, "{name}_sum {sum}").expect("Failed to write metrics");
+        writeln!(output, "{name}_count {count}").expect("Failed to write metrics");
+    }
+    output
+}
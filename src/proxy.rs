@@ -0,0 +1,203 @@
+//! Client address, scheme and host resolution behind trusted reverse proxies.
+
+use axum::http::HeaderMap;
+use axum::http::header::{FORWARDED, HOST};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+const X_FORWARDED_HOST: &str = "x-forwarded-host";
+
+/// Determine the client's IP address for a request received from `peer`.
+///
+/// Forwarding headers are only honored when `peer` is within one of the `trusted` networks;
+/// otherwise they could be spoofed by anyone and `peer` itself is the client. The `Forwarded`
+/// header takes precedence over `X-Forwarded-For`. The chain of hops is walked from the
+/// nearest one, skipping trusted proxies, so a client cannot spoof entries added after its own.
+#[must_use]
+pub fn client_ip(peer: Option<IpAddr>, headers: &HeaderMap, trusted: &[IpNet]) -> Option<IpAddr> {
+    let peer = peer?;
+    let is_trusted = |ip: &IpAddr| trusted.iter().any(|net| net.contains(ip));
+    if !is_trusted(&peer) {
+        return Some(peer);
+    }
+
+    let hops = forwarded_hops(headers);
+    let mut client = peer;
+    for hop in hops.into_iter().rev() {
+        let Some(hop) = hop else {
+            // An obfuscated or unparsable hop ends what can be trusted.
+            break;
+        };
+        client = hop;
+        if !is_trusted(&hop) {
+            break;
+        }
+    }
+    Some(client)
+}
+
+/// Determine the scheme the client used for a request received from `peer`, `http` or `https`.
+///
+/// As with [`client_ip`], forwarding headers are only honored when `peer` is within one of the
+/// `trusted` networks. Only the values added by the nearest proxy, the last ones, are used, as
+/// the client controls any before them. The `proto` of the last `Forwarded` element takes
+/// precedence over the last `X-Forwarded-Proto` value.
+#[must_use]
+pub fn forwarded_scheme(
+    peer: Option<IpAddr>,
+    headers: &HeaderMap,
+    trusted: &[IpNet],
+) -> &'static str {
+    if !is_trusted_peer(peer, trusted) {
+        return "http";
+    }
+    let proto = nearest_forwarded(headers, "proto").or_else(|| last(headers, X_FORWARDED_PROTO));
+    match proto {
+        Some(proto) if proto.eq_ignore_ascii_case("https") => "https",
+        _ => "http",
+    }
+}
+
+/// Determine the host, with an optional port, the client addressed a request received from
+/// `peer` with.
+///
+/// The `host` of the last `Forwarded` element or the last `X-Forwarded-Host` value is used when
+/// `peer` is trusted, as with [`forwarded_scheme`], and the `Host` header otherwise. Values
+/// that are not a plain host and port are ignored.
+#[must_use]
+pub fn forwarded_host(
+    peer: Option<IpAddr>,
+    headers: &HeaderMap,
+    trusted: &[IpNet],
+) -> Option<String> {
+    let forwarded = if is_trusted_peer(peer, trusted) {
+        nearest_forwarded(headers, "host").or_else(|| last(headers, X_FORWARDED_HOST))
+    } else {
+        None
+    };
+    let host = forwarded.or_else(|| last(headers, HOST.as_str()))?;
+    let is_plain = !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ':' | '[' | ']'));
+    is_plain.then(|| host.to_ascii_lowercase())
+}
+
+fn is_trusted_peer(peer: Option<IpAddr>, trusted: &[IpNet]) -> bool {
+    peer.is_some_and(|peer| trusted.iter().any(|net| net.contains(&peer)))
+}
+
+/// The last comma-separated value of the header `name`, across all of its lines.
+fn last<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .last()
+        .map(str::trim)
+}
+
+/// The parameter `key` of the last `Forwarded` element, the one added by the nearest proxy.
+fn nearest_forwarded<'a>(headers: &'a HeaderMap, key: &str) -> Option<&'a str> {
+    last(headers, FORWARDED.as_str())?
+        .split(';')
+        .find_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            name.eq_ignore_ascii_case(key).then_some(value)
+        })
+        .map(|value| value.trim().trim_matches('"'))
+}
+
+/// The hops listed in the forwarding headers, from the original client to the nearest proxy.
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let forwarded: Vec<_> = headers
+        .get_all(FORWARDED)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|element| {
+            element
+                .split(';')
+                .find_map(|pair| {
+                    let (key, value) = pair.trim().split_once('=')?;
+                    key.eq_ignore_ascii_case("for").then_some(value)
+                })
+                .and_then(parse_node)
+        })
+        .collect();
+    if !forwarded.is_empty() {
+        return forwarded;
+    }
+
+    headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(parse_node)
+        .collect()
+}
+
+/// Parse a node as found in forwarding headers, e.g. `192.0.2.1`, `"[2001:db8::1]:4711"`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    node.parse::<IpAddr>()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+        .or_else(|| {
+            node.strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|ip| ip.parse().ok())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_client_ip_trusted_proxies() {
+        let trusted: Vec<IpNet> = vec!["10.0.0.0/8".parse().unwrap()];
+        let ip = |ip: &str| ip.parse::<IpAddr>().unwrap();
+        let headers = |name: &'static str, value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, HeaderValue::from_static(value));
+            headers
+        };
+        let xff = headers("x-forwarded-for", "198.51.100.7, 10.0.0.2");
+
+        // Untrusted peers cannot spoof their address.
+        assert_eq!(
+            client_ip(Some(ip("203.0.113.9")), &xff, &trusted),
+            Some(ip("203.0.113.9"))
+        );
+        // Trusted proxies are skipped from the nearest hop on.
+        assert_eq!(
+            client_ip(Some(ip("10.0.0.1")), &xff, &trusted),
+            Some(ip("198.51.100.7"))
+        );
+        // A client-supplied entry before the real client is ignored.
+        let spoofed = headers("x-forwarded-for", "1.2.3.4, 198.51.100.7");
+        assert_eq!(
+            client_ip(Some(ip("10.0.0.1")), &spoofed, &trusted),
+            Some(ip("198.51.100.7"))
+        );
+        // `Forwarded` is preferred and may carry quoted IPv6 addresses with ports.
+        let mut forwarded = headers("forwarded", r#"for="[2001:db8::1]:4711";proto=https"#);
+        forwarded.insert("x-forwarded-for", HeaderValue::from_static("198.51.100.7"));
+        assert_eq!(
+            client_ip(Some(ip("10.0.0.1")), &forwarded, &trusted),
+            Some(ip("2001:db8::1"))
+        );
+        // Without headers the trusted peer is the client; without a peer there is no client.
+        assert_eq!(
+            client_ip(Some(ip("10.0.0.1")), &HeaderMap::new(), &trusted),
+            Some(ip("10.0.0.1"))
+        );
+        assert_eq!(client_ip(None, &xff, &trusted), None);
+    }
+}
@@ -0,0 +1,150 @@
+//! Detection of configured bangs whose services are no longer reachable.
+
+use crate::bang::Bang;
+use tokio::task::JoinSet;
+use tracing::warn;
+use url::Url;
+
+/// The address probed for a bang: its domain if set, otherwise the origin of its URL template.
+#[must_use]
+pub fn probe_url(bang: &Bang) -> Option<Url> {
+    if let Some(domain) = &bang.domain {
+        let url = if domain.contains("://") {
+            Url::parse(domain)
+        } else {
+            Url::parse(&format!("https://{domain}"))
+        };
+        return url.ok();
+    }
+    let mut url = Url::parse(&bang.url_template).ok()?;
+    url.set_path("/");
+    url.set_query(None);
+    url.set_fragment(None);
+    Some(url)
+}
+
+/// Configured bangs split by whether their service could be reached.
+#[derive(Debug, Default)]
+pub struct ProbeResults {
+    /// Bangs that responded, or whose probe failed for a reason other than the service being
+    /// gone, such as a timeout or a TLS error. In their original order.
+    pub kept: Vec<Bang>,
+    /// Bangs whose service could not be connected to, or that have no URL to probe.
+    pub dead: Vec<Bang>,
+    /// How many probes got an HTTP response.
+    pub responded: usize,
+}
+
+/// What probing a bang found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Probe {
+    Responded,
+    Dead,
+    Inconclusive,
+}
+
+/// Split bangs into those whose service is gone and those to keep, keeping order.
+///
+/// Any HTTP response counts as alive, and only failing to connect, including failing to
+/// resolve the host, means the service is gone. Other errors prove nothing either way, so
+/// those bangs are kept. Bangs without a URL that can be probed are reported as dead.
+pub async fn partition_dead_bangs(client: &reqwest::Client, bangs: Vec<Bang>) -> ProbeResults {
+    let mut probes = JoinSet::new();
+    for (index, bang) in bangs.iter().enumerate() {
+        let client = client.clone();
+        let url = probe_url(bang);
+        probes.spawn(async move {
+            let probe = match url {
+                Some(url) => match client.head(url).send().await {
+                    Ok(_) => Probe::Responded,
+                    Err(e) if e.is_connect() => Probe::Dead,
+                    Err(_) => Probe::Inconclusive,
+                },
+                None => Probe::Dead,
+            };
+            (index, probe)
+        });
+    }
+    let mut outcomes = vec![Probe::Inconclusive; bangs.len()];
+    while let Some(probe) = probes.join_next().await {
+        if let Ok((index, probe)) = probe {
+            outcomes[index] = probe;
+        }
+    }
+
+    let mut results = ProbeResults::default();
+    for (bang, probe) in bangs.into_iter().zip(outcomes) {
+        match probe {
+            Probe::Responded => {
+                results.responded += 1;
+                results.kept.push(bang);
+            }
+            Probe::Inconclusive => {
+                warn!(
+                    "Could not tell whether '{}' is reachable, keeping it.",
+                    bang.trigger
+                );
+                results.kept.push(bang);
+            }
+            Probe::Dead => results.dead.push(bang),
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_partition_dead_bangs() {
+        let alive = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let alive_addr = alive.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(alive, axum::Router::new()).await.unwrap();
+        });
+        // Accepts connections into its backlog but never answers, so requests time out.
+        let silent = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let silent_addr = silent.local_addr().unwrap();
+        // Nothing listens on a port once its listener is dropped.
+        let dead_addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let alive_bang = Bang::new(
+            "alive",
+            format!("http://{alive_addr}/search?q={{{{{{s}}}}}}"),
+        );
+        let silent_bang = Bang::new("silent", format!("http://{silent_addr}/?q="));
+        let dead_bang = Bang {
+            domain: Some(format!("http://{dead_addr}")),
+            ..Bang::new("dead", "https://example.com/?q={{{s}}}")
+        };
+        assert_eq!(
+            probe_url(&alive_bang).unwrap().as_str(),
+            format!("http://{alive_addr}/")
+        );
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(500))
+            .build()
+            .unwrap();
+        let results =
+            partition_dead_bangs(&client, vec![silent_bang, dead_bang.clone(), alive_bang]).await;
+        let triggers = |bangs: &[Bang]| {
+            bangs
+                .iter()
+                .map(|bang| bang.trigger.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(triggers(&results.kept), ["silent", "alive"]);
+        assert_eq!(triggers(&results.dead), ["dead"]);
+        assert_eq!(results.responded, 1);
+
+        let results = partition_dead_bangs(&client, vec![dead_bang]).await;
+        assert_eq!(results.responded, 0);
+        drop(silent);
+    }
+}
@@ -0,0 +1,154 @@
+//! Per-client token bucket rate limiting.
+
+use parking_lot::Mutex;
+use std::collections::{BTreeSet, HashMap};
+use std::net::{IpAddr, Ipv6Addr};
+use std::time::{Duration, Instant};
+
+/// Most clients tracked at once, the least recently seen one is forgotten to make room.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    by_client: HashMap<IpAddr, Bucket>,
+    /// The clients in `by_client` by when their bucket was last updated, oldest first.
+    by_age: BTreeSet<(Instant, IpAddr)>,
+}
+
+/// Token buckets keyed by client IP.
+///
+/// Each client may burst up to `per_min` requests, and regains tokens at `per_min` per minute.
+/// The limit is passed on every check so configuration reloads apply immediately.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    /// Take a token for `client` at `now`.
+    ///
+    /// IPv6 clients share a bucket per /64 network, as one host is usually handed a whole /64.
+    ///
+    /// # Errors
+    /// If the client has no tokens left, with how long until the next one is available.
+    pub fn check(&self, client: IpAddr, per_min: u32, now: Instant) -> Result<(), Duration> {
+        let client = bucket_key(client);
+        let capacity = f64::from(per_min);
+        let per_sec = capacity / 60.0;
+        let mut buckets = self.buckets.lock();
+        let Buckets { by_client, by_age } = &mut *buckets;
+        if !by_client.contains_key(&client)
+            && by_client.len() >= MAX_TRACKED_CLIENTS
+            && let Some((_, oldest)) = by_age.pop_first()
+        {
+            by_client.remove(&oldest);
+        }
+        let bucket = by_client.entry(client).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        by_age.remove(&(bucket.updated, client));
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = elapsed.mul_add(per_sec, bucket.tokens).min(capacity);
+        bucket.updated = bucket.updated.max(now);
+        by_age.insert((bucket.updated, client));
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_sec))
+        }
+    }
+}
+
+/// The address `client` is rate limited by: IPv4 addresses as they are and IPv6 ones by their
+/// /64 network.
+fn bucket_key(client: IpAddr) -> IpAddr {
+    match client {
+        IpAddr::V4(_) => client,
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => IpAddr::V4(ip),
+            None => IpAddr::V6(Ipv6Addr::from(u128::from(ip) & !u128::from(u64::MAX))),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::default();
+        let client: IpAddr = [192, 0, 2, 1].into();
+        let other: IpAddr = [192, 0, 2, 2].into();
+        let start = Instant::now();
+
+        assert!(limiter.check(client, 2, start).is_ok());
+        assert!(limiter.check(client, 2, start).is_ok());
+        assert_eq!(
+            limiter.check(client, 2, start),
+            Err(Duration::from_secs(30))
+        );
+        // Buckets are per client.
+        assert!(limiter.check(other, 2, start).is_ok());
+        // Two per minute refill one token every 30 seconds.
+        assert!(
+            limiter
+                .check(client, 2, start + Duration::from_secs(30))
+                .is_ok()
+        );
+        assert!(
+            limiter
+                .check(client, 2, start + Duration::from_secs(30))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_rate_limiter_ipv6_networks() {
+        let limiter = RateLimiter::default();
+        let start = Instant::now();
+        let ip = |ip: &str| ip.parse::<IpAddr>().unwrap();
+
+        assert!(limiter.check(ip("2001:db8:0:1::1"), 1, start).is_ok());
+        // Another address in the same /64 shares the bucket.
+        assert!(limiter.check(ip("2001:db8:0:1:ffff::2"), 1, start).is_err());
+        assert!(limiter.check(ip("2001:db8:0:2::1"), 1, start).is_ok());
+        // IPv4-mapped addresses count as the IPv4 address.
+        assert!(limiter.check(ip("192.0.2.1"), 1, start).is_ok());
+        assert!(limiter.check(ip("::ffff:192.0.2.1"), 1, start).is_err());
+    }
+
+    #[test]
+    fn test_rate_limiter_evicts_oldest_client() {
+        let limiter = RateLimiter::default();
+        let start = Instant::now();
+        let client = |i: u32| IpAddr::from(std::net::Ipv4Addr::from(0x0a00_0000 + i));
+
+        assert!(limiter.check(client(0), 1, start).is_ok());
+        for i in 1..u32::try_from(MAX_TRACKED_CLIENTS).unwrap() {
+            assert!(
+                limiter
+                    .check(client(i), 1, start + Duration::from_millis(1))
+                    .is_ok()
+            );
+        }
+        // Tracking one more client forgets the least recently seen one, and only that one.
+        let later = start + Duration::from_millis(2);
+        assert!(
+            limiter
+                .check(IpAddr::from([192, 0, 2, 1]), 1, later)
+                .is_ok()
+        );
+        assert_eq!(limiter.buckets.lock().by_client.len(), MAX_TRACKED_CLIENTS);
+        assert!(limiter.check(client(1), 1, later).is_err());
+        assert!(limiter.check(client(0), 1, later).is_ok());
+    }
+}
@@ -0,0 +1,3223 @@
+//! The HTTP server: route handlers, middleware and the router wiring them together.
+
+use crate::bang::{Bang, Category, RedirectStatus};
+use crate::config::{
+    AppConfig, AppState, NoQueryAction, SuggestionProvider, append_file_config, reload_bangs_only,
+    rewrite_file_bangs,
+};
+use crate::proxy::{client_ip, forwarded_host, forwarded_scheme};
+use crate::stats::BangHits;
+use crate::{
+    BANG_CACHE, BangMap, MatchKind, bang_suggestions, decode_search_term, http_client, is_ready,
+    reindex_triggers, resolve_batch, resolve_with_lang, search_param,
+};
+use axum::extract::{ConnectInfo, FromRequest, FromRequestParts, RawQuery, Request, State};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri, header};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::post;
+use axum::{Form, Json, Router, extract::Query, response::Redirect, routing::get};
+use heck::ToTitleCase;
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::{Level, error, field, warn};
+
+/// Response header carrying the trigger of the matched bang, or `default` when none matched.
+const BANG_HEADER: &str = "x-redirector-bang";
+
+/// Request header carrying the `admin_token`, an alternative to `Authorization: Bearer`.
+const ADMIN_TOKEN_HEADER: &str = "x-admin-token";
+
+/// Served when a query resolves to a malformed URL and the fallback chain found no alternative.
+const UNRESOLVED_PAGE: &str = "<!DOCTYPE html><html><head><meta charset=\"UTF-8\"><title>Cannot resolve query</title></head><body><h1>Cannot resolve query</h1><p>The query resolved to a malformed URL and no fallback succeeded. Check the bang template and <code>default_search</code> in the configuration.</p></body></html>";
+
+/// The client's IP address, read from forwarding headers when the peer is a trusted proxy.
+///
+/// `None` when the peer address is unknown, e.g. on a Unix socket.
+struct ClientIp(Option<IpAddr>);
+
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        Ok(Self(client_ip(
+            peer,
+            &parts.headers,
+            &state.read_config().trusted_proxies,
+        )))
+    }
+}
+
+/// The scheme and host the client reached the server with, e.g. `https://search.example.com`.
+///
+/// Taken from trusted forwarding headers or the `Host` header, falling back to the address the
+/// server is configured to listen on.
+struct Origin(String);
+
+impl FromRequestParts<AppState> for Origin {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let app_config = state.read_config();
+        let trusted = &app_config.trusted_proxies;
+        let scheme = forwarded_scheme(peer, &parts.headers, trusted);
+        let host = forwarded_host(peer, &parts.headers, trusted)
+            .unwrap_or_else(|| SocketAddr::new(app_config.ip, app_config.port).to_string());
+        Ok(Self(format!("{scheme}://{host}")))
+    }
+}
+
+/// The language the client prefers most, from the `Accept-Language` header, e.g. `de-AT`.
+struct PreferredLanguage(Option<String>);
+
+impl<S: Send + Sync> FromRequestParts<S> for PreferredLanguage {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header = parts
+            .headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok());
+        Ok(Self(header.and_then(preferred_language)))
+    }
+}
+
+/// The tag with the highest quality in an `Accept-Language` value, the first one on ties.
+fn preferred_language(accept_language: &str) -> Option<String> {
+    let mut best: Option<(&str, f32)> = None;
+    for range in accept_language.split(',') {
+        let mut params = range.split(';');
+        let tag = params.next().unwrap_or_default().trim();
+        let quality = params
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .map_or(1.0, |q| q.trim().parse().unwrap_or(0.0));
+        if tag.is_empty() || tag == "*" || quality <= 0.0 {
+            continue;
+        }
+        if best.is_none_or(|(_, best_quality)| quality > best_quality) {
+            best = Some((tag, quality));
+        }
+    }
+    best.map(|(tag, _)| tag.to_string())
+}
+
+/// Reject clients that exceed `rate_limit_per_min` with `429 Too Many Requests`.
+///
+/// Does nothing when the limit is `0` or the client address is unknown.
+async fn rate_limit(
+    State(app_state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    request: Request,
+    next: Next,
+) -> Response {
+    let per_min = app_state.read_config().rate_limit_per_min;
+    if per_min > 0
+        && let Some(client_ip) = client_ip
+        && let Err(retry_after) = app_state
+            .rate_limiter
+            .check(client_ip, per_min, Instant::now())
+    {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(
+                header::RETRY_AFTER,
+                retry_after.as_secs_f64().ceil().to_string(),
+            )],
+            "Too many requests, please retry later.",
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+/// Marks a response that [`access_log`] must not log, for bangs with `no_log = true`.
+#[derive(Debug, Clone, Copy)]
+struct NoLog;
+
+/// Emit `event` at a level only known at runtime, as `tracing` macros need a constant level.
+macro_rules! event_at {
+    ($level:expr, $($event:tt)+) => {
+        match $level {
+            Level::TRACE => tracing::event!(Level::TRACE, $($event)+),
+            Level::DEBUG => tracing::event!(Level::DEBUG, $($event)+),
+            Level::INFO => tracing::event!(Level::INFO, $($event)+),
+            Level::WARN => tracing::event!(Level::WARN, $($event)+),
+            Level::ERROR => tracing::event!(Level::ERROR, $($event)+),
+        }
+    };
+}
+
+/// Log one line per request with its method, path, status, client IP, bang and latency.
+///
+/// Redirects are logged at `redirect_log_level`, everything else at `info`.
+async fn access_log(
+    State(app_state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+    let start = Instant::now();
+    let response = next.run(request).await;
+    let latency_us = u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX);
+    if response.extensions().get::<NoLog>().is_some() {
+        return response;
+    }
+    let status = response.status();
+    let level = if status.is_redirection() {
+        app_state.read_config().redirect_log_level.level()
+    } else {
+        Some(Level::INFO)
+    };
+    if let Some(level) = level {
+        let bang = response
+            .headers()
+            .get(BANG_HEADER)
+            .and_then(|value| value.to_str().ok());
+        event_at!(
+            level,
+            %method,
+            path,
+            status = status.as_u16(),
+            client_ip = client_ip.map(field::display),
+            bang,
+            latency_us,
+            "access"
+        );
+    }
+    response
+}
+
+/// Reject requests without the configured `admin_token` with `401 Unauthorized`.
+///
+/// The token is accepted as `Authorization: Bearer <token>` or in an `X-Admin-Token` header.
+/// Does nothing when no token is configured.
+async fn require_admin_token(
+    State(app_state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(admin_token) = app_state.read_config().admin_token.clone() else {
+        return next.run(request).await;
+    };
+    let headers = request.headers();
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let token = headers
+        .get(ADMIN_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok());
+    if [bearer, token]
+        .into_iter()
+        .flatten()
+        .any(|given| tokens_match(given.trim(), &admin_token))
+    {
+        return next.run(request).await;
+    }
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        Json(serde_json::json!({ "status": "failed", "error": "missing or wrong admin token" })),
+    )
+        .into_response()
+}
+
+/// Compare tokens in time independent of where they differ, so it does not leak the token.
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// The endpoints that change the configuration, guarded by `admin_token`.
+fn admin_routes(app_state: &AppState) -> Router<AppState> {
+    Router::new()
+        .route("/add_bang", post(add_bang))
+        .route("/reload_bangs", post(reload_bangs))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            require_admin_token,
+        ))
+}
+
+/// Headers sent with every HTML page unless `security_headers` overrides them. The pages only
+/// use inline scripts and styles, and form posts leave for the bang's site.
+const SECURITY_HEADERS: [(&str, &str); 4] = [
+    (
+        "content-security-policy",
+        "default-src 'none'; script-src 'unsafe-inline'; style-src 'unsafe-inline'; img-src 'self' data:; form-action 'self' http: https:; base-uri 'none'; frame-ancestors 'none'",
+    ),
+    ("x-content-type-options", "nosniff"),
+    ("referrer-policy", "no-referrer"),
+    ("x-frame-options", "DENY"),
+];
+
+/// Middleware adding the security headers to HTML pages. Redirects carry no content for them
+/// to protect.
+async fn security_headers(
+    State(app_state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("text/html"));
+    if !is_html {
+        return response;
+    }
+    let config = app_state.read_config();
+    let overrides = config
+        .security_headers
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()));
+    let headers = response.headers_mut();
+    for (name, value) in SECURITY_HEADERS.into_iter().chain(overrides) {
+        let Ok(name) = HeaderName::from_bytes(name.as_bytes()) else {
+            continue;
+        };
+        if value.is_empty() {
+            headers.remove(name);
+        } else if let Ok(value) = HeaderValue::from_str(value) {
+            headers.insert(name, value);
+        }
+    }
+    response
+}
+
+/// Handler function that extracts the `q` parameter and redirects accordingly
+async fn handler(
+    RawQuery(raw_query): RawQuery,
+    PreferredLanguage(lang): PreferredLanguage,
+    State(app_state): State<AppState>,
+) -> Response {
+    let query = {
+        let config = app_state.read_config();
+        raw_query.and_then(|raw_query| {
+            search_param(&raw_query, &config.query_param, config.plus_as_space)
+        })
+    };
+    search(query, lang.as_deref(), &app_state)
+}
+
+/// Like [`handler`], but with the query in the path, as in `/s/rust+programming`.
+async fn path_search(
+    uri: Uri,
+    PreferredLanguage(lang): PreferredLanguage,
+    State(app_state): State<AppState>,
+) -> Response {
+    let query = uri
+        .path()
+        .strip_prefix("/s/")
+        .filter(|term| !term.is_empty())
+        .map(|term| decode_search_term(term, app_state.read_config().plus_as_space));
+    search(query, lang.as_deref(), &app_state)
+}
+
+/// Redirect to where `query` resolves, or answer as `no_query_action` says without one.
+fn search(query: Option<String>, lang: Option<&str>, app_state: &AppState) -> Response {
+    if !is_ready() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Bang commands are still loading, please retry shortly.",
+        )
+            .into_response();
+    }
+    let config = app_state.read_config();
+    let mut response = query.map_or_else(
+        || no_query_response(&config),
+        |query| {
+            let outcome = resolve_with_lang(&config, &query, lang);
+            if !outcome.no_log
+                && let Some(trigger) = outcome.trigger()
+            {
+                app_state.stats.record(trigger);
+            }
+            let mut response = if outcome.kind == MatchKind::Unresolved {
+                (StatusCode::INTERNAL_SERVER_ERROR, Html(UNRESOLVED_PAGE)).into_response()
+            } else {
+                let bang = HeaderValue::from_str(outcome.trigger().unwrap_or("default"))
+                    .unwrap_or_else(|_| HeaderValue::from_static("default"));
+                if let Some(form) = &outcome.form {
+                    let page = post_form_page(&outcome.url, form, outcome.confirm);
+                    ([(BANG_HEADER, bang)], Html(page)).into_response()
+                } else if outcome.confirm {
+                    ([(BANG_HEADER, bang)], Html(confirm_page(&outcome.url))).into_response()
+                } else {
+                    if config.no_referrer_redirects {
+                        (
+                            [
+                                (
+                                    header::REFERRER_POLICY,
+                                    HeaderValue::from_static("no-referrer"),
+                                ),
+                                (HeaderName::from_static(BANG_HEADER), bang),
+                            ],
+                            Html(refresh_page(&outcome.url)),
+                        )
+                            .into_response()
+                    } else {
+                        let status = outcome.status.unwrap_or(config.redirect_status);
+                        redirect(status, &outcome.url, bang)
+                    }
+                }
+            };
+            if outcome.no_log {
+                response.extensions_mut().insert(NoLog);
+            }
+            response
+        },
+    );
+    // The destination depends on the bang list and configuration, which may change at any time.
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
+
+/// Redirect to `url` with `status`, tagged with the bang that was used.
+fn redirect(status: RedirectStatus, url: &str, bang: HeaderValue) -> Response {
+    let Ok(location) = HeaderValue::from_str(url) else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Html(UNRESOLVED_PAGE)).into_response();
+    };
+    let status = StatusCode::from_u16(status.code()).unwrap_or(StatusCode::SEE_OTHER);
+    (
+        status,
+        [
+            (header::LOCATION, location),
+            (HeaderName::from_static(BANG_HEADER), bang),
+        ],
+    )
+        .into_response()
+}
+
+/// What `/` answers with when there is no query, as chosen by `no_query_action`.
+fn no_query_response(config: &AppConfig) -> Response {
+    match &config.no_query_action {
+        NoQueryAction::BangsPage => {
+            Redirect::to(&format!("{}/bangs", config.base_path)).into_response()
+        }
+        NoQueryAction::Homepage(url) => Redirect::to(url).into_response(),
+        NoQueryAction::BadRequest => {
+            (StatusCode::BAD_REQUEST, "Missing the q query parameter.").into_response()
+        }
+        NoQueryAction::SearchBox => Html(search_box_page(&config.base_path)).into_response(),
+    }
+}
+
+/// A page with a search box submitting its query to `/`.
+fn search_box_page(base_path: &str) -> String {
+    let action = escape_html(&format!("{base_path}/"));
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"UTF-8\"><title>Redirector</title></head><body><form action=\"{action}\" method=\"get\"><input type=\"search\" name=\"q\" autofocus> <button type=\"submit\">Search</button></form></body></html>"
+    )
+}
+
+/// Inline script filtering the rows of every bang table by the search box and category buttons,
+/// keeping the category of the form in sync so submitting it searches every page alike.
+const FILTER_SCRIPT: &str = "<script>const filter = document.getElementById('filter'); const categoryInput = document.getElementById('category'); let category = categoryInput.value; function applyFilter() { const q = filter.value.toLowerCase(); document.querySelectorAll('tr.bang').forEach((row) => { row.hidden = !row.textContent.toLowerCase().includes(q) || (category !== '' && row.dataset.category !== category); }); } filter.addEventListener('input', applyFilter); document.querySelectorAll('button[data-category]').forEach((button) => button.addEventListener('click', () => { category = button.dataset.category; categoryInput.value = category; document.querySelectorAll('button[data-category]').forEach((other) => other.classList.toggle('active', other === button)); applyFilter(); }));</script>";
+
+/// Stylesheet for the bangs page, dark by default and light when preferred or chosen.
+const BANGS_STYLE: &str = "<style>:root { --bg: #181818; --bg-alt: #161616; --fg: #ffffff; --border: #ffffff10; color-scheme: dark; background: var(--bg); color: var(--fg); font-family: monospace; } @media (prefers-color-scheme: light) { :root:not([data-theme=dark]) { --bg: #ffffff; --bg-alt: #f4f4f4; --fg: #181818; --border: #18181810; color-scheme: light; } } :root[data-theme=light] { --bg: #ffffff; --bg-alt: #f4f4f4; --fg: #181818; --border: #18181810; color-scheme: light; } table { border-collapse: collapse; width: 100vw; } table th { text-align: left; padding: 1rem 0; font-size: 1.25rem; width: 100vw; } table tr { border-bottom: var(--border) solid 2px; } table tr:nth-child(2n) { background: var(--bg-alt); } table tr:nth-child(2n+1) { background: var(--bg); } input, button { background: var(--bg-alt); color: var(--fg); font-family: monospace; border: var(--border) solid 2px; padding: 0.5rem; margin: 0 0.5rem 0.5rem 0; } button.active { border-color: var(--fg); }</style>";
+
+/// Inline script restoring the theme saved in `localStorage`, unless one was requested explicitly.
+const THEME_SCRIPT: &str = "<script>if (!document.documentElement.dataset.theme && localStorage.getItem('theme')) { document.documentElement.dataset.theme = localStorage.getItem('theme'); }</script>";
+
+/// Inline script flipping between the light and dark theme and remembering the choice.
+const THEME_TOGGLE_SCRIPT: &str = "<script>document.getElementById('theme-toggle').addEventListener('click', () => { const root = document.documentElement; const current = root.dataset.theme || (matchMedia('(prefers-color-scheme: light)').matches ? 'light' : 'dark'); root.dataset.theme = current === 'light' ? 'dark' : 'light'; localStorage.setItem('theme', root.dataset.theme); });</script>";
+
+#[derive(Debug, Default, Deserialize)]
+struct BangsParams {
+    /// Force the `light` or `dark` theme instead of the saved or preferred one.
+    theme: Option<String>,
+    /// With `1`, serve only a bare table of the active bangs, for embedding in other pages.
+    plain: Option<String>,
+    /// The page of active bangs to show, starting at 1. Ignored unless a number.
+    page: Option<String>,
+    /// How many active bangs to show per page, [`DEFAULT_BANGS_PER_PAGE`] unless a number.
+    per: Option<String>,
+    /// Only show bangs whose trigger, URL or description contains this, case-insensitively.
+    filter: Option<String>,
+    /// Only show bangs of the category with this name.
+    category: Option<String>,
+}
+
+/// How many active bangs the bangs page shows at once unless `per` is given.
+const DEFAULT_BANGS_PER_PAGE: usize = 500;
+
+/// The most active bangs the bangs page shows at once, whatever `per` asks for.
+const MAX_BANGS_PER_PAGE: usize = 5000;
+
+/// A page that submits `fields` as a form POST to `action`, for bangs with `method = "post"`.
+///
+/// The form is submitted as soon as the page loads, unless the bang asked to `confirm` it.
+fn post_form_page(action: &str, fields: &[(String, String)], confirm: bool) -> String {
+    let action = escape_html(action);
+    let mut html = String::from(
+        "<!DOCTYPE html><html><head><meta charset=\"UTF-8\"><title>Redirecting</title></head>",
+    );
+    if confirm {
+        write!(
+            html,
+            "<body><h1>Leaving Redirector</h1><p>This search is sent to <code>{action}</code>.</p>"
+        )
+        .unwrap();
+    } else {
+        html.push_str("<body onload=\"document.forms[0].submit()\">");
+    }
+    write!(html, "<form method=\"post\" action=\"{action}\">").unwrap();
+    for (name, value) in fields {
+        write!(
+            html,
+            "<input type=\"hidden\" name=\"{}\" value=\"{}\">",
+            escape_html(name),
+            escape_html(value)
+        )
+        .unwrap();
+    }
+    if confirm {
+        html.push_str("<button type=\"submit\">Continue</button></form></body></html>");
+    } else {
+        html.push_str(
+            "<noscript><button type=\"submit\">Continue</button></noscript></form></body></html>",
+        );
+    }
+    html
+}
+
+/// A page showing where a bang with `confirm = true` leads, with a link to continue there.
+fn confirm_page(url: &str) -> String {
+    let url = escape_html(url);
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"UTF-8\"><title>Leaving Redirector</title></head><body><h1>Leaving Redirector</h1><p>This search leads to <code>{url}</code>.</p><p><a href=\"{url}\" rel=\"noreferrer\">Continue</a></p></body></html>"
+    )
+}
+
+/// A page that immediately moves on to `url` without sending the search page as `Referer`.
+fn refresh_page(url: &str) -> String {
+    let url = escape_html(url);
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"UTF-8\"><meta name=\"referrer\" content=\"no-referrer\"><meta http-equiv=\"refresh\" content=\"0; url={url}\"><title>Redirecting</title></head><body><p>Redirecting to <a href=\"{url}\" rel=\"noreferrer\">{url}</a>.</p></body></html>"
+    )
+}
+
+/// Escape text for use in HTML content and attribute values.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+async fn list_bangs(
+    Query(params): Query<BangsParams>,
+    State(app_state): State<AppState>,
+) -> Html<String> {
+    if matches!(params.plain.as_deref(), Some("1" | "true")) {
+        let mut html = String::new();
+        write_plain_bangs(&mut html, &BANG_CACHE.read());
+        return Html(html);
+    }
+    let pkg_name = env!("CARGO_PKG_NAME").to_title_case();
+    let theme = match params.theme.as_deref() {
+        Some(theme @ ("light" | "dark")) => Some(theme),
+        _ => None,
+    };
+    let base_path = app_state.read_config().base_path.clone();
+    let theme_attr = theme
+        .map(|theme| format!(r#" data-theme="{theme}""#))
+        .unwrap_or_default();
+    let mut html = format!(
+        r#"<!DOCTYPE html><html{theme_attr}><head><meta charset="UTF-8"><meta name="viewport" content="width=device-width, initial-scale=1.0"><link rel="search" type="application/opensearchdescription+xml" title="{pkg_name}" href="{base_path}/opensearch.xml"/><link rel="manifest" href="{base_path}/manifest.json"/><title>Bang Commands</title>{BANGS_STYLE}{THEME_SCRIPT}</head><body><h1>Bang Commands</h1><button id="theme-toggle" type="button" aria-label="Toggle light and dark theme">Toggle theme</button>"#
+    );
+
+    // The script filters the rendered rows as the filter is typed or a category is chosen.
+    // Submitting the filter searches every page of bangs here instead.
+    let filter = params.filter.as_deref().unwrap_or_default().trim();
+    let category = params.category.as_deref().and_then(|name| {
+        Category::ALL
+            .into_iter()
+            .find(|category| category.to_string() == name)
+    });
+    let filter_lower = filter.to_lowercase();
+    let shown = |bang: &Bang| {
+        category.is_none_or(|category| bang.category == Some(category))
+            && [
+                Some(&bang.trigger),
+                Some(&bang.url_template),
+                bang.description.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+            .any(|text| text.to_lowercase().contains(&filter_lower))
+    };
+    let per = params
+        .per
+        .and_then(|per| per.parse().ok())
+        .unwrap_or(DEFAULT_BANGS_PER_PAGE)
+        .clamp(1, MAX_BANGS_PER_PAGE);
+    let category_name = category.map(|category| category.to_string());
+    write!(
+        html,
+        r#"<form method="get" action="{base_path}/bangs"><input id="filter" type="search" name="filter" value="{}" placeholder="Filter by trigger or URL" autofocus><input id="category" type="hidden" name="category" value="{}"><input type="hidden" name="per" value="{per}">{}<button type="submit">Search all pages</button></form>"#,
+        escape_html(filter),
+        category_name.as_deref().unwrap_or_default(),
+        theme
+            .map(|theme| format!(r#"<input type="hidden" name="theme" value="{theme}">"#))
+            .unwrap_or_default(),
+    )
+    .expect("Failed to write to HTML string");
+    let active = |is_active: bool| if is_active { r#" class="active""# } else { "" };
+    write!(
+        html,
+        r#"<div><button type="button"{} data-category="">All</button>"#,
+        active(category.is_none())
+    )
+    .expect("Failed to write to HTML string");
+    for option in Category::ALL {
+        write!(
+            html,
+            r#"<button type="button"{} data-category="{option}">{option}</button>"#,
+            active(category == Some(option))
+        )
+        .expect("Failed to write to HTML string");
+    }
+    html.push_str("</div>");
+
+    if let Some(bangs) = &app_state.get_config().bangs {
+        html.push_str("<h2>Configured Bangs</h2><table><th>Abbr.</th><th>Trigger</th><th>URL</th><th>Description</th>");
+        for bang in bangs.iter().filter(|bang| shown(bang)) {
+            let category = bang
+                .category
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default();
+            write!(
+                html,
+                r#"<tr class="bang" data-category="{category}"><td><strong>{}</strong></td><td>{}</td><td>{}</td><td>{}</td></tr>"#,
+                escape_html(&format!("{:?}", bang.short_name)),
+                escape_html(&bang.trigger),
+                escape_html(&bang.url_template),
+                escape_html(bang.description.as_deref().unwrap_or_default())
+            )
+            .expect("Failed to write to HTML string");
+        }
+        html.push_str("</table>");
+    }
+
+    html.push_str("<h2>Active Bangs</h2>");
+    let cache = BANG_CACHE.read();
+    let mut bangs = sorted_active_bangs(&cache);
+    bangs.retain(|bang| shown(bang));
+    let pages = bangs.len().div_ceil(per).max(1);
+    let page = params
+        .page
+        .and_then(|page| page.parse().ok())
+        .unwrap_or(1)
+        .clamp(1, pages);
+    let start = (page - 1) * per;
+    write_active_bangs(&mut html, &bangs[start..bangs.len().min(start + per)]);
+    let mut link_params = String::new();
+    for (name, value) in [
+        ("filter", Some(filter).filter(|filter| !filter.is_empty())),
+        ("category", category_name.as_deref()),
+        ("theme", theme),
+    ] {
+        if let Some(value) = value {
+            write!(link_params, "&amp;{name}={}", urlencoding::encode(value))
+                .expect("Failed to write to HTML string");
+        }
+    }
+    write_page_links(&mut html, &base_path, page, pages, per, &link_params);
+    html.push_str(FILTER_SCRIPT);
+    html.push_str(THEME_TOGGLE_SCRIPT);
+    html.push_str("</body></html>");
+    Html(html)
+}
+
+/// The active bangs as JSON, in the same order as the bangs page so the output only changes
+/// with the bang list.
+async fn bangs_json() -> Response {
+    let cache = BANG_CACHE.read();
+    Json(sorted_active_bangs(&cache)).into_response()
+}
+
+/// The active bangs in the order the bangs page and `/bangs.json` list them.
+///
+/// The page groups bangs by category and subcategory, so they are sorted by category first, in
+/// the order of [`Category::ALL`] with bangs without one last, then by subcategory. Within each
+/// group the most relevant bangs come first, ties broken by trigger.
+///
+/// The caller passes the locked cache, as the returned bangs borrow from its read guard. Each
+/// bang carries its own trigger, so the cache keys are not returned alongside.
+fn sorted_active_bangs(cache: &BangMap) -> Vec<&Bang> {
+    let category_index = |bang: &Bang| {
+        bang.category
+            .and_then(|category| Category::ALL.iter().position(|c| *c == category))
+            .unwrap_or(Category::ALL.len())
+    };
+    let mut bangs: Vec<&Bang> = cache.values().collect();
+    bangs.sort_unstable_by(|a, b| {
+        category_index(a)
+            .cmp(&category_index(b))
+            .then_with(|| a.subcategory.cmp(&b.subcategory))
+            .then_with(|| b.relevance.cmp(&a.relevance))
+            .then_with(|| a.trigger.cmp(&b.trigger))
+    });
+    bangs
+}
+
+/// Write bangs sorted by [`sorted_active_bangs`] as one collapsible section per category, with
+/// a table per subcategory. Bangs without a category go into "Uncategorized".
+fn write_active_bangs(html: &mut String, bangs: &[&Bang]) {
+    for bangs in bangs.chunk_by(|a, b| a.category == b.category) {
+        let category = bangs[0].category;
+        let name = category.map_or_else(|| "Uncategorized".to_string(), |c| c.to_string());
+        let data_category = category.map(|c| c.to_string()).unwrap_or_default();
+        write!(html, "<details><summary>{name} ({})</summary>", bangs.len())
+            .expect("Failed to write to HTML string");
+
+        for subcategory in bangs.chunk_by(|a, b| a.subcategory == b.subcategory) {
+            if let Some(name) = &subcategory[0].subcategory {
+                write!(html, "<h3>{}</h3>", escape_html(name))
+                    .expect("Failed to write to HTML string");
+            }
+            html.push_str("<table><th>Trigger</th><th>URL</th>");
+            for bang in subcategory {
+                write!(
+                    html,
+                    r#"<tr class="bang" data-category="{data_category}"><td><strong>{}</strong></td><td>{}</td></tr>"#,
+                    escape_html(&bang.trigger),
+                    escape_html(&bang.url_template)
+                )
+                .expect("Failed to write to HTML string");
+            }
+            html.push_str("</table>");
+        }
+        html.push_str("</details>");
+    }
+}
+
+/// Write links to the previous and next page of active bangs, if there are any.
+///
+/// `params` are appended to both links, already escaped, e.g. `&amp;theme=dark`.
+fn write_page_links(
+    html: &mut String,
+    base_path: &str,
+    page: usize,
+    pages: usize,
+    per: usize,
+    params: &str,
+) {
+    if pages == 1 {
+        return;
+    }
+    html.push_str("<nav>");
+    if page > 1 {
+        write!(
+            html,
+            r#"<a href="{base_path}/bangs?page={}&amp;per={per}{params}" rel="prev">Previous</a> "#,
+            page - 1
+        )
+        .expect("Failed to write to HTML string");
+    }
+    write!(html, "Page {page} of {pages}").expect("Failed to write to HTML string");
+    if page < pages {
+        write!(
+            html,
+            r#" <a href="{base_path}/bangs?page={}&amp;per={per}{params}" rel="next">Next</a>"#,
+            page + 1
+        )
+        .expect("Failed to write to HTML string");
+    }
+    html.push_str("</nav>");
+}
+
+/// Write the active bangs as a single unstyled table sorted by trigger.
+fn write_plain_bangs(html: &mut String, cache: &BangMap) {
+    let mut bangs: Vec<&Bang> = cache.values().collect();
+    bangs.sort_unstable_by(|a, b| a.trigger.cmp(&b.trigger));
+    html.push_str("<table><tr><th>Trigger</th><th>URL</th></tr>");
+    for bang in bangs {
+        write!(
+            html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            escape_html(&bang.trigger),
+            escape_html(&bang.url_template)
+        )
+        .expect("Failed to write to HTML string");
+    }
+    html.push_str("</table>");
+}
+
+/// The OpenSearch description, with URLs using the scheme the client connected with so
+/// browsers on an HTTPS deployment accept them.
+async fn opensearch(
+    Origin(origin): Origin,
+    State(app_state): State<AppState>,
+) -> impl IntoResponse {
+    let pkg_name = env!("CARGO_PKG_NAME");
+    let pkg_description = env!("CARGO_PKG_DESCRIPTION");
+    let app_config = app_state.get_config();
+    let root = format!("{origin}{}", app_config.base_path);
+    // Both routes read the query from `query_param` with a GET, as advertised here.
+    let param = escape_html(&urlencoding::encode(&app_config.query_param));
+    let suggestions_url = if app_config.enable_suggestions {
+        format!(
+            "  <Url type=\"application/x-suggestions+json\" method=\"GET\" template=\"{root}/suggest?{param}={{searchTerms}}\" />\n"
+        )
+    } else {
+        String::new()
+    };
+    let opensearch_xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<OpenSearchDescription
+  xmlns="http://a9.com/-/spec/opensearch/1.1/"
+  xmlns:moz="http://www.mozilla.org/2006/browser/search/">
+  <ShortName>{}</ShortName>
+  <Description>{}</Description>
+  <InputEncoding>UTF-8</InputEncoding>
+  <Image height="64" width="64">data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAEAAAABACAYAAACqaXHeAAAACXBIWXMAADsOAAA7DgHMtqGDAAAAGXRFWHRTb2Z0d2FyZQB3d3cuaW5rc2NhcGUub3Jnm+48GgAABA9JREFUeJztm8trVVcUxn831WAoqB2pra9kVCWKOC21tA4sIoivkZFSHxEUpDP9AzootEXpRBpfYAoFHwMFHThRYlBBdKBodaBttEnsJPFRjd5SPwfrRBNzc88+r72veD8I5N691jprfdlnr7XX3ilJ4n1GQ2gHQqNOQGgHQqNOQGgHQqNOQGgHQiMkAZ8AR4EzwJcZbU0CPgd2AieA+8C/wO44xVKgQqgFuApMiT4PAouBvxLY+AhYAawEvgY+jL4XUIp+fzziGZUhKcTPIY3FAUfdBkk/SxqqYONt/BlnL0TwsyWVKzhbljTTQX+1Q+CS9FLS8Th7IdaAdmBihe8nAlsd9C8Bz4CXMXIl4GKcMd8ENAKbq4xvjmSqoQ8jsRQjBzVIwBpgWpXx6cAqBzsXgBcxMk+Ay3GGfBOw3UFmm4PMj8CEGJnTQDnOkE8CFgKfOcgtAVqrjLcAy4gn4KSLUz4JaE8guyVmbOQ68T823cWbhfEZcMrpSZ5SX5OkQcf0JUkDkU4lW72RzAtJTyQdkTRL0npJT6OxH1x980XANwmCH0bbOLbuSron6XtJzW+NzZC0UlYsOfnmqxTuxu39H4ku4IsCfBkFHwTMB26k0FOkeytfd0bDxyL4bUq9ErAxT0cqPqTgGdAI9GAFTho8AObgkM/ToqgZMB/4iWzBE+n2YIXPvBz8GoM8Z0ATsA7L90kXPFd0A/uwRspQHgbzIGABFnQbMDWzR254CPwGdADXsxhKS0Aj1olpB5bitjMrClcwIjpJMSuSEjAb27NvovquLgT+AfYDv2I9QTc4VkwtsjZWpU5OraEs6aDGVompK8GvgOP4e7/zwiMsG/VVE3JJgzt594IH6wbviRNyIeBRdl+CYXKcgMsr8DFwk7j+eu1hAFgLnK0m5DID+oBFwAHgv+x+FY4y5utiYoKH5GlwJpb7t5CtxC0C/Vga7AD+dlVKWwh9ACwHdlAbhdAvwO+kmKF5lMKt2IzYgJ3X+cAgVvl1kK7X8Bp5boYmYYtOO3ZSWwS6sM3QMeB5HgaL6gd8ijUz2oAZGW31YxufgxTQHar1hkg/MJd3sCEyjDL210uLTgoMHvw0RedhhVRSKNK9na87o+GjKfoH1slJii4KDh78HY3t86STGL4ORpqwktp1VzmAXaLKJdVVg68ZMESyxbATD8GD31tiC4BrjrKtZKzwXOHzePw6cN5B7hyeggf/N0T25iSTG3xflGwE7jF+R7kfOwrz1nfwPQPK2J59POzHc9MlxFXZWcAdxt4VLGP3f3p9OhPiouR9KqfEw3gOHsJdlm7GOjnDDZSHWN+xx7cjoQgAuyK/Cwv+O2wB9I6QBNQE6v8xEtqB0KgTENqB0KgTENqB0HjvCXgFiecDVd5zzR0AAAAASUVORK5CYII=</Image>
+  <Url type="text/html" method="GET" template="{root}/?{param}={{searchTerms}}" />
+{suggestions_url}  <moz:SearchForm>{root}/bangs</moz:SearchForm>
+</OpenSearchDescription>"#,
+        pkg_name.to_title_case(),
+        pkg_description,
+    );
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/opensearchdescription+xml"),
+    );
+    // Shorter-lived than the icon, the templates change with `base_path`.
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=86400"),
+    );
+    // The templates point at the origin the client used, so caches must tell them apart.
+    headers.insert(
+        header::VARY,
+        HeaderValue::from_static("Forwarded, X-Forwarded-Proto, X-Forwarded-Host, Host"),
+    );
+    (StatusCode::OK, headers, opensearch_xml)
+}
+
+/// The icon also embedded in `/opensearch.xml`, served as the favicon.
+const ICON_PNG: &[u8] = include_bytes!("../res/icon64.png");
+
+/// `Cache-Control` of the favicon and manifest, which only change with a new release.
+const STATIC_CACHE_CONTROL: &str = "public, max-age=604800";
+
+async fn favicon() -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "image/png"),
+            (header::CACHE_CONTROL, STATIC_CACHE_CONTROL),
+        ],
+        ICON_PNG,
+    )
+}
+
+/// A minimal web app manifest, so the search page can be installed.
+async fn manifest(State(app_state): State<AppState>) -> impl IntoResponse {
+    let pkg_name = env!("CARGO_PKG_NAME");
+    let base_path = app_state.read_config().base_path.clone();
+    let manifest = serde_json::json!({
+        "name": pkg_name.to_title_case(),
+        "short_name": pkg_name,
+        "start_url": format!("{base_path}/bangs"),
+        "display": "browser",
+        "icons": [{
+            "src": format!("{base_path}/favicon.ico"),
+            "sizes": "64x64",
+            "type": "image/png",
+        }],
+    });
+    (
+        [
+            (header::CONTENT_TYPE, "application/manifest+json"),
+            (header::CACHE_CONTROL, STATIC_CACHE_CONTROL),
+        ],
+        manifest.to_string(),
+    )
+}
+
+/// How long `search_suggestions` may take to answer, suggestions are useless once the user has
+/// typed on.
+const SUGGESTIONS_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Suggestions for the query, read from the same parameter as the search itself.
+async fn suggestions_proxy(
+    RawQuery(raw_query): RawQuery,
+    State(app_state): State<AppState>,
+) -> impl IntoResponse {
+    crate::metrics::increment(&crate::metrics::SUGGESTION_REQUESTS);
+    let query = {
+        let config = app_state.read_config();
+        raw_query
+            .and_then(|raw_query| {
+                search_param(&raw_query, &config.query_param, config.plus_as_space)
+            })
+            .unwrap_or_default()
+    };
+    if !app_state.read_config().enable_suggestions {
+        return suggestions_response(StatusCode::OK, &query, &[]);
+    }
+    if let Some(suggestions) = bang_suggestions(&query) {
+        // Upstream knows nothing about bangs, so complete the trigger being typed instead.
+        return suggestions_response(StatusCode::OK, &query, &suggestions);
+    }
+    let app_config = app_state.get_config();
+    let search_suggestions = &app_config.search_suggestions;
+    if query.is_empty() || !search_suggestions.contains("{}") {
+        // Without a placeholder upstream would only return results unrelated to the query.
+        return suggestions_response(StatusCode::OK, &query, &[]);
+    }
+    let suggest_api_url = suggestions_url(search_suggestions, &query);
+
+    let started = Instant::now();
+    let fetched = fetch_suggestions(&suggest_api_url, app_config.suggestions_max_bytes).await;
+    crate::metrics::SUGGESTION_LATENCY.observe(started.elapsed());
+    match fetched {
+        Ok(json) => {
+            let suggestions =
+                json.and_then(|json| normalize_suggestions(&app_config.suggestion_provider, json));
+            if suggestions.is_none() {
+                crate::metrics::increment(&crate::metrics::SUGGESTION_UPSTREAM_ERRORS);
+                warn!("Discarded an oversized or malformed response from {suggest_api_url}");
+            }
+            suggestions_response(StatusCode::OK, &query, &suggestions.unwrap_or_default())
+        }
+        Err(e) => {
+            crate::metrics::increment(&crate::metrics::SUGGESTION_UPSTREAM_ERRORS);
+            error!("Failed to fetch suggestions from {suggest_api_url}: {e}");
+            suggestions_response(StatusCode::INTERNAL_SERVER_ERROR, &query, &[])
+        }
+    }
+}
+
+/// The media type of OpenSearch suggestions, as advertised in `/opensearch.xml`.
+const SUGGESTIONS_CONTENT_TYPE: &str = "application/x-suggestions+json";
+
+/// Answer with `suggestions` for `query` in the `[query, [suggestions]]` OpenSearch form.
+fn suggestions_response(status: StatusCode, query: &str, suggestions: &[String]) -> Response {
+    (
+        status,
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(SUGGESTIONS_CONTENT_TYPE),
+        )],
+        Json(serde_json::json!([query, suggestions])),
+    )
+        .into_response()
+}
+
+/// Fill the `search_suggestions` template with the percent-encoded query, so reserved
+/// characters like `&` and `#` cannot change the upstream URL.
+fn suggestions_url(template: &str, query: &str) -> String {
+    template.replace("{}", &urlencoding::encode(query))
+}
+
+/// Fetch suggestions from `url`, or `None` if the response is larger than `max_bytes` or is not
+/// JSON.
+async fn fetch_suggestions(
+    url: &str,
+    max_bytes: usize,
+) -> reqwest::Result<Option<serde_json::Value>> {
+    let mut response = http_client()
+        .get(url)
+        .timeout(SUGGESTIONS_TIMEOUT)
+        .send()
+        .await?;
+    if response
+        .content_length()
+        .is_some_and(|length| length > max_bytes as u64)
+    {
+        return Ok(None);
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > max_bytes {
+            return Ok(None);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(serde_json::from_slice(&body).ok())
+}
+
+/// The completions in a response of `provider`, or `None` if it does not have the shape that
+/// provider answers with.
+fn normalize_suggestions(
+    provider: &SuggestionProvider,
+    json: serde_json::Value,
+) -> Option<Vec<String>> {
+    let json = match provider {
+        SuggestionProvider::Brave | SuggestionProvider::Custom(_) => json,
+        // The OpenSearch layout, followed by descriptions, an empty list and Google metadata.
+        SuggestionProvider::Google => serde_json::Value::Array(json.as_array()?.get(..2)?.to_vec()),
+        // A list of `{"phrase": completion}` objects.
+        SuggestionProvider::DuckDuckGo => {
+            return json
+                .as_array()?
+                .iter()
+                .map(|entry| Some(entry.get("phrase")?.as_str()?.to_string()))
+                .collect();
+        }
+    };
+    if !is_opensearch_suggestions(&json) {
+        return None;
+    }
+    json[1]
+        .as_array()?
+        .iter()
+        .map(|completion| completion.as_str().map(str::to_string))
+        .collect()
+}
+
+/// Whether `json` is an OpenSearch suggestions response: the query, the completions and
+/// optionally their descriptions and URLs, all as strings.
+fn is_opensearch_suggestions(json: &serde_json::Value) -> bool {
+    let Some([query, lists @ ..]) = json.as_array().map(Vec::as_slice) else {
+        return false;
+    };
+    query.is_string()
+        && (1..=3).contains(&lists.len())
+        && lists.iter().all(|list| {
+            list.as_array()
+                .is_some_and(|items| items.iter().all(serde_json::Value::is_string))
+        })
+}
+
+async fn metrics() -> impl IntoResponse {
+    (
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; version=0.0.4"),
+        )],
+        crate::metrics::render(),
+    )
+}
+
+/// A bang sent to `add_bang`, either as a JSON body, a form-encoded body or, for older
+/// clients, as query parameters.
+struct BangPayload(Bang);
+
+impl<S: Send + Sync> FromRequest<S> for BangPayload {
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+
+        if content_type.starts_with("application/json") {
+            Json::<Bang>::from_request(req, state)
+                .await
+                .map(|Json(bang)| Self(bang))
+                .map_err(IntoResponse::into_response)
+        } else if content_type.starts_with("application/x-www-form-urlencoded") {
+            Form::<Bang>::from_request(req, state)
+                .await
+                .map(|Form(bang)| Self(bang))
+                .map_err(IntoResponse::into_response)
+        } else {
+            Query::<Bang>::from_request(req, state)
+                .await
+                .map(|Query(bang)| Self(bang))
+                .map_err(IntoResponse::into_response)
+        }
+    }
+}
+
+/// How often each bang was used since startup, most used first.
+async fn stats(State(app_state): State<AppState>) -> Json<Vec<BangHits>> {
+    Json(app_state.stats.snapshot())
+}
+
+/// Reload the configured bangs from the configuration file without fetching remote bangs.
+async fn reload_bangs(State(app_state): State<AppState>) -> impl IntoResponse {
+    if reload_bangs_only(&app_state) {
+        (
+            StatusCode::OK,
+            Json(serde_json::json!({ "status": "success" })),
+        )
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "status": "failed", "error": "no configuration file found" })),
+        )
+    }
+}
+
+/// Liveness probe, succeeds whenever the process is serving requests.
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// The running build and a summary of its configuration.
+///
+/// The summary lists only options that cannot carry secrets or reveal the network around the
+/// server, so e.g. URL templates, which may hold API keys, are left out and bangs only counted.
+async fn version(State(app_state): State<AppState>) -> Json<serde_json::Value> {
+    let config = app_state.read_config();
+    let summary = serde_json::json!({
+        "port": config.port,
+        "base_path": config.base_path,
+        "query_param": config.query_param,
+        "bangs": config.bangs.as_ref().map_or(0, Vec::len),
+        "bang_lists": config.bangs_url.len(),
+        "builtin_bangs": config.builtin_bangs,
+        "admin_token_set": config.admin_token.is_some(),
+        "enable_suggestions": config.enable_suggestions,
+        "suggestion_provider": config.suggestion_provider,
+        "default_template_mode": config.default_template_mode,
+        "no_query_action": config.no_query_action,
+        "redirect_status": config.redirect_status,
+        "resolve_fallback_chain": config.resolve_fallback_chain,
+        "rate_limit_per_min": config.rate_limit_per_min,
+        "rate_limit_all_routes": config.rate_limit_all_routes,
+        "reject_shrunk_bangs": config.reject_shrunk_bangs,
+    });
+    Json(serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_commit": env!("REDIRECTOR_GIT_COMMIT"),
+        "build_timestamp": env!("REDIRECTOR_BUILD_TIMESTAMP").parse::<u64>().unwrap_or_default(),
+        "listening": *app_state.bound_addrs.read(),
+        "config": summary,
+    }))
+}
+
+/// Readiness probe, succeeds once [`is_ready`], the same check searches wait for.
+async fn readyz() -> impl IntoResponse {
+    if is_ready() {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not ready")
+    }
+}
+
+/// Where the `query_param` parameter would redirect to, as JSON, without redirecting or counting
+/// it in the usage stats.
+async fn resolve_preview(
+    RawQuery(raw_query): RawQuery,
+    PreferredLanguage(lang): PreferredLanguage,
+    State(app_state): State<AppState>,
+) -> Response {
+    let config = app_state.read_config();
+    let Some(query) = raw_query
+        .and_then(|raw_query| search_param(&raw_query, &config.query_param, config.plus_as_space))
+    else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "status": "failed",
+                "error": format!("missing the {} query parameter", config.query_param),
+            })),
+        )
+            .into_response();
+    };
+    Json(resolve_with_lang(&config, &query, lang.as_deref())).into_response()
+}
+
+/// Maximum number of queries accepted by a single `batch_resolve` request.
+const MAX_BATCH_SIZE: usize = 100;
+
+/// Resolve a JSON array of queries, returning their outcomes in the same order.
+async fn batch_resolve(
+    State(app_state): State<AppState>,
+    Json(queries): Json<Vec<String>>,
+) -> Response {
+    if queries.len() > MAX_BATCH_SIZE {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(serde_json::json!({
+                "status": "failed",
+                "error": format!("at most {MAX_BATCH_SIZE} queries can be resolved at once"),
+            })),
+        )
+            .into_response();
+    }
+    Json(resolve_batch(&app_state.read_config(), &queries)).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct AddBangParams {
+    /// Replace a configured bang with the same trigger instead of rejecting the request.
+    overwrite: Option<bool>,
+}
+
+// endpoint to add a new bang to the config file
+async fn add_bang(
+    State(app_state): State<AppState>,
+    Query(add_params): Query<AddBangParams>,
+    BangPayload(params): BangPayload,
+) -> impl IntoResponse {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+
+    if let Err(e) = params.validate() {
+        return (
+            StatusCode::BAD_REQUEST,
+            headers,
+            Json(serde_json::json!({ "status": "failed", "error": e.to_string() })),
+        );
+    }
+
+    let mut config = app_state.config.write();
+    let max_user_bangs = config.max_user_bangs;
+    if let Some(bangs) = &mut config.bangs {
+        if let Some(existing) = bangs
+            .iter_mut()
+            .find(|bang| bang.trigger.eq_ignore_ascii_case(&params.trigger))
+        {
+            if !add_params.overwrite.unwrap_or(false) {
+                return (
+                    StatusCode::CONFLICT,
+                    headers,
+                    Json(serde_json::json!({
+                        "status": "failed",
+                        "error": format!("a bang with trigger '{}' already exists", params.trigger),
+                    })),
+                );
+            }
+            if let Some(mut cache) = BANG_CACHE.try_write() {
+                cache.remove(&existing.key());
+            }
+            *existing = params.clone();
+            rewrite_file_bangs(&app_state.config_path, bangs);
+        } else {
+            if bangs.len() >= max_user_bangs {
+                return (
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    headers,
+                    Json(serde_json::json!({
+                        "status": "failed",
+                        "error": format!("at most {max_user_bangs} bangs can be configured"),
+                    })),
+                );
+            }
+            append_file_config(&app_state.config_path, params.clone());
+            bangs.push(params.clone());
+        }
+        if let Some(mut cache) = BANG_CACHE.try_write() {
+            cache.insert(params.key(), params.configured());
+        }
+        reindex_triggers();
+        return (
+            StatusCode::OK,
+            headers,
+            Json(serde_json::json!({ "status": "success" })),
+        );
+    }
+    drop(config);
+
+    (
+        StatusCode::BAD_REQUEST,
+        headers,
+        Json(serde_json::json!({ "status": "failed" })),
+    )
+}
+
+/// Allow the configured origins to call the routes of `router` cross-origin, answering
+/// preflight requests. Without any allowed origins the router is left same-origin only.
+fn with_cors(router: Router<AppState>, allowed_origins: &[String]) -> Router<AppState> {
+    if allowed_origins.is_empty() {
+        return router;
+    }
+    let allow_origin = if allowed_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(allowed_origins.iter().filter_map(|origin| {
+            HeaderValue::from_str(origin)
+                .inspect_err(|_| warn!("Ignoring invalid allowed origin '{}'", origin))
+                .ok()
+        }))
+    };
+    router.layer(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods([Method::GET, Method::POST])
+            .allow_headers([header::CONTENT_TYPE]),
+    )
+}
+
+/// The router serving every endpoint of redirector, with its middleware applied.
+///
+/// The routes are served under `base_path`. Mount the router in a larger axum app with
+/// [`Router::merge`] or [`Router::nest`]. Which routes are rate limited, the allowed origins and
+/// the base path are read from the configuration once, here.
+pub fn build_router(app_state: AppState) -> Router {
+    let (rate_limit_all_routes, allowed_origins, base_path) = {
+        let config = app_state.read_config();
+        (
+            config.rate_limit_all_routes,
+            config.allowed_origins.clone(),
+            config.base_path.clone(),
+        )
+    };
+    let rate_limited = middleware::from_fn_with_state(app_state.clone(), rate_limit);
+    let suggest = if rate_limit_all_routes {
+        get(suggestions_proxy)
+    } else {
+        get(suggestions_proxy).route_layer(rate_limited.clone())
+    };
+    let api = Router::new()
+        .route("/suggest", suggest)
+        .route("/stats", get(stats))
+        .route("/resolve", get(resolve_preview))
+        .route("/bangs.json", get(bangs_json))
+        .route("/api/batch_resolve", post(batch_resolve));
+    let mut app = Router::new()
+        .route("/", get(handler))
+        .route("/s/", get(path_search))
+        .route("/s/{*query}", get(path_search))
+        .route("/bangs", get(list_bangs))
+        .route("/opensearch.xml", get(opensearch))
+        .route("/favicon.ico", get(favicon))
+        .route("/manifest.json", get(manifest))
+        .merge(admin_routes(&app_state))
+        .route("/metrics", get(metrics))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/version", get(version))
+        .merge(with_cors(api, &allowed_origins));
+    if !base_path.is_empty() {
+        // `nest` only matches the bare base path, so also serve the search page with a slash.
+        app = Router::new()
+            .route(&format!("{base_path}/"), get(handler))
+            .nest(&base_path, app);
+    }
+    // Applied after nesting so the search page with a slash is limited as well.
+    if rate_limit_all_routes {
+        app = app.layer(rate_limited);
+    }
+    app.layer(middleware::from_fn_with_state(
+        app_state.clone(),
+        security_headers,
+    ))
+    .layer(middleware::from_fn_with_state(
+        app_state.clone(),
+        access_log,
+    ))
+    .with_state(app_state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bang::BangMethod;
+    use crate::config::{Config, FallbackStep, FileConfig, LogLevel, get_file_config};
+    use std::io;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+    use tower::ServiceExt;
+
+    /// An empty configuration file in a temporary directory, which is removed once dropped.
+    fn config_file() -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "").unwrap();
+        (dir, path)
+    }
+
+    fn add_bang_request(trigger: &str, url_template: &str, query: &str) -> Request {
+        Request::post(format!("/add_bang{query}"))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(axum::body::Body::from(
+                serde_json::json!({ "trigger": trigger, "url_template": url_template }).to_string(),
+            ))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_handler_bang_header() {
+        let app_state = AppState::new(AppConfig::default());
+        BANG_CACHE.write().insert(
+            "headertest".to_string(),
+            Bang::new("headertest", "https://example.com/?q={{{s}}}"),
+        );
+
+        let response = handler(
+            RawQuery(Some("q=!headertest+rust".to_string())),
+            PreferredLanguage(None),
+            State(app_state.clone()),
+        )
+        .await;
+        assert_eq!(response.headers()[BANG_HEADER], "headertest");
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://example.com/?q=rust"
+        );
+        let Json(hits) = stats(State(app_state.clone())).await;
+        assert_eq!(
+            hits,
+            [BangHits {
+                trigger: "headertest".to_string(),
+                count: 1
+            }]
+        );
+
+        let response = handler(
+            RawQuery(Some("q=rust%20programming".to_string())),
+            PreferredLanguage(None),
+            State(app_state),
+        )
+        .await;
+        assert_eq!(response.headers()[BANG_HEADER], "default");
+    }
+
+    #[tokio::test]
+    async fn test_handler_no_log() {
+        let app_state = AppState::new(AppConfig::default());
+        BANG_CACHE.write().extend([
+            (
+                "nologhidden".to_string(),
+                Bang {
+                    no_log: Some(true),
+                    ..Bang::new("nologhidden", "https://example.com/?q={{{s}}}")
+                },
+            ),
+            (
+                "nologshown".to_string(),
+                Bang::new("nologshown", "https://example.com/?q={{{s}}}"),
+            ),
+        ]);
+
+        for query in ["q=!nologhidden+rash", "q=!nologshown+rust"] {
+            let response = handler(
+                RawQuery(Some(query.to_string())),
+                PreferredLanguage(None),
+                State(app_state.clone()),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        }
+        let Json(hits) = stats(State(app_state)).await;
+        assert_eq!(
+            hits,
+            [BangHits {
+                trigger: "nologshown".to_string(),
+                count: 1
+            }]
+        );
+    }
+
+    /// Log lines written by a test's `tracing` subscriber.
+    #[derive(Clone, Default)]
+    struct LogBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl io::Write for LogBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl LogBuffer {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_access_log() {
+        let logs = LogBuffer::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_max_level(Level::TRACE)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+        BANG_CACHE.write().extend([
+            (
+                "accesslogtest".to_string(),
+                Bang::new("accesslogtest", "https://example.com/?q={{{s}}}"),
+            ),
+            (
+                "accesslognolog".to_string(),
+                Bang {
+                    no_log: Some(true),
+                    ..Bang::new("accesslognolog", "https://example.com/?q={{{s}}}")
+                },
+            ),
+        ]);
+        let app = |redirect_log_level| {
+            let app_state = AppState::new(AppConfig {
+                redirect_log_level,
+                ..AppConfig::default()
+            });
+            Router::new()
+                .route("/", get(handler))
+                .route("/healthz", get(healthz))
+                .layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    access_log,
+                ))
+                .with_state(app_state)
+        };
+        let request = |uri: &str| {
+            let mut request = Request::get(uri).body(axum::body::Body::empty()).unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from(([192, 0, 2, 1], 4711))));
+            request
+        };
+
+        app(LogLevel::Debug)
+            .oneshot(request("/?q=!accesslogtest+secret"))
+            .await
+            .unwrap();
+        let line = logs.take();
+        assert_eq!(line.lines().count(), 1, "{line}");
+        assert!(line.contains("DEBUG"), "{line}");
+        assert!(line.contains("method=GET path=\"/\" status=303"), "{line}");
+        assert!(line.contains("client_ip=192.0.2.1"), "{line}");
+        assert!(line.contains("bang=\"accesslogtest\""), "{line}");
+        assert!(line.contains("latency_us="), "{line}");
+        assert!(!line.contains("secret"), "{line}");
+
+        app(LogLevel::Off)
+            .oneshot(request("/?q=!accesslogtest+rust"))
+            .await
+            .unwrap();
+        assert_eq!(logs.take(), "");
+        app(LogLevel::Off)
+            .oneshot(request("/healthz"))
+            .await
+            .unwrap();
+        let line = logs.take();
+        assert!(line.contains("INFO"), "{line}");
+        assert!(line.contains("path=\"/healthz\" status=200"), "{line}");
+
+        app(LogLevel::Info)
+            .oneshot(request("/?q=!accesslognolog+rust"))
+            .await
+            .unwrap();
+        assert_eq!(logs.take(), "");
+    }
+
+    #[tokio::test]
+    async fn test_handler_unresolved() {
+        BANG_CACHE.write().insert(
+            "unresolvedtest".to_string(),
+            Bang::new("unresolvedtest", "example.com/?q={{{s}}}"),
+        );
+        let config = AppConfig {
+            resolve_fallback_chain: vec![FallbackStep::ErrorPage],
+            ..AppConfig::default()
+        };
+
+        let response = handler(
+            RawQuery(Some("q=!unresolvedtest+rust".to_string())),
+            PreferredLanguage(None),
+            State(AppState::new(config)),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(response.headers().get(header::LOCATION).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_handler_redirect_status() {
+        let mut permanent = Bang::new("statustest", "https://example.com/?q={{{s}}}");
+        permanent.status = Some(RedirectStatus::MovedPermanently);
+        BANG_CACHE
+            .write()
+            .insert("statustest".to_string(), permanent);
+        BANG_CACHE.write().insert(
+            "nostatustest".to_string(),
+            Bang::new("nostatustest", "https://example.com/?q={{{s}}}"),
+        );
+        let config: FileConfig = toml::from_str("redirect_status = 307").unwrap();
+        let app_state = AppState::new(config.merge(Config::default()));
+
+        let response = handler(
+            RawQuery(Some("q=!statustest+rust".to_string())),
+            PreferredLanguage(None),
+            State(app_state.clone()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://example.com/?q=rust"
+        );
+        assert_eq!(response.headers()[BANG_HEADER], "statustest");
+
+        let response = handler(
+            RawQuery(Some("q=!nostatustest+rust".to_string())),
+            PreferredLanguage(None),
+            State(app_state),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+
+        assert!(toml::from_str::<FileConfig>("redirect_status = 200").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handler_no_referrer_redirects() {
+        BANG_CACHE.write().insert(
+            "referrertest".to_string(),
+            Bang::new("referrertest", "https://example.com/?q={{{s}}}&a=b"),
+        );
+        let config = AppConfig {
+            no_referrer_redirects: true,
+            ..AppConfig::default()
+        };
+
+        let response = handler(
+            RawQuery(Some("q=!referrertest+rust".to_string())),
+            PreferredLanguage(None),
+            State(AppState::new(config)),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::LOCATION).is_none());
+        assert_eq!(response.headers()[header::REFERRER_POLICY], "no-referrer");
+        assert_eq!(response.headers()[BANG_HEADER], "referrertest");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.contains(
+            r#"<meta http-equiv="refresh" content="0; url=https://example.com/?q=rust&amp;a=b">"#
+        ));
+        assert!(html.contains(r#"<meta name="referrer" content="no-referrer">"#));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_preview() {
+        BANG_CACHE.write().insert(
+            "previewtest".to_string(),
+            Bang::new("previewtest", "https://example.com/?q={{{s}}}"),
+        );
+        let app_state = AppState::new(AppConfig::default());
+        async fn preview(
+            app_state: &AppState,
+            query: Option<&str>,
+        ) -> (StatusCode, serde_json::Value) {
+            let response = resolve_preview(
+                RawQuery(query.map(str::to_string)),
+                PreferredLanguage(None),
+                State(app_state.clone()),
+            )
+            .await;
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, serde_json::from_slice(&body).unwrap())
+        }
+
+        let (status, json) = preview(&app_state, Some("q=!previewtest+rust")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "url": "https://example.com/?q=rust",
+                "kind": "bang_matched",
+                "trigger": "previewtest",
+                "source": "remote",
+            })
+        );
+        let (_, json) = preview(&app_state, Some("q=rust")).await;
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "url": "https://www.qwant.com/?q=rust",
+                "kind": "default_search",
+                "source": "default",
+            })
+        );
+        let (status, json) = preview(&app_state, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["error"], "missing the q query parameter");
+        let Json(hits) = stats(State(app_state)).await;
+        assert!(hits.is_empty());
+
+        // The error names the configured parameter.
+        let app_state = AppState::new(AppConfig {
+            query_param: "search".to_string(),
+            ..AppConfig::default()
+        });
+        let (status, json) = preview(&app_state, Some("other=rust")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["error"], "missing the search query parameter");
+    }
+
+    #[tokio::test]
+    async fn test_handler_no_query_action() {
+        async fn no_query(no_query_action: NoQueryAction) -> Response {
+            let config = AppConfig {
+                no_query_action,
+                base_path: "/search".to_string(),
+                ..AppConfig::default()
+            };
+            handler(
+                RawQuery(None),
+                PreferredLanguage(None),
+                State(AppState::new(config)),
+            )
+            .await
+        }
+
+        let response = no_query(NoQueryAction::BangsPage).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[header::LOCATION], "/search/bangs");
+
+        let action: FileConfig =
+            toml::from_str(r#"no_query_action = { homepage = "https://example.com/" }"#).unwrap();
+        let response = no_query(action.no_query_action.unwrap()).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[header::LOCATION], "https://example.com/");
+
+        let response = no_query(NoQueryAction::BadRequest).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = no_query(NoQueryAction::SearchBox).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(
+            html.contains(r#"<form action="/search/" method="get"><input type="search" name="q""#)
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let app_state = AppState::new(AppConfig {
+            rate_limit_per_min: 1,
+            ..AppConfig::default()
+        });
+        let app = Router::new()
+            .route(
+                "/suggest",
+                get(healthz).route_layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    rate_limit,
+                )),
+            )
+            .with_state(app_state);
+        let request = |ip: [u8; 4]| {
+            let mut request = Request::get("/suggest")
+                .body(axum::body::Body::empty())
+                .unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from((ip, 4711))));
+            request
+        };
+
+        let response = app.clone().oneshot(request([192, 0, 2, 1])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.clone().oneshot(request([192, 0, 2, 1])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "60");
+        let response = app.oneshot(request([192, 0, 2, 2])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // With every route limited, each way to the search page under a base path counts.
+        let app = build_router(AppState::new(AppConfig {
+            rate_limit_per_min: 1,
+            rate_limit_all_routes: true,
+            base_path: "/search".to_string(),
+            ..AppConfig::default()
+        }));
+        let request = |uri: &str| {
+            let mut request = Request::get(uri).body(axum::body::Body::empty()).unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from(([192, 0, 2, 3], 4711))));
+            request
+        };
+        let response = app.clone().oneshot(request("/search")).await.unwrap();
+        assert_ne!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let response = app.oneshot(request("/search/")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_cors_allowed_origins() {
+        let app = with_cors(
+            Router::new().route("/stats", get(stats)),
+            &["https://extension.example".to_string()],
+        )
+        .with_state(AppState::new(AppConfig::default()));
+        let request = |method: Method, origin: &str| {
+            Request::builder()
+                .method(method)
+                .uri("/stats")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+                .body(axum::body::Body::empty())
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(request(Method::GET, "https://extension.example"))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://extension.example"
+        );
+        let response = app
+            .clone()
+            .oneshot(request(Method::GET, "https://evil.example"))
+            .await
+            .unwrap();
+        assert!(
+            response
+                .headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .is_none()
+        );
+        let response = app
+            .oneshot(request(Method::OPTIONS, "https://extension.example"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://extension.example"
+        );
+
+        let any = with_cors(
+            Router::new().route("/stats", get(stats)),
+            &["*".to_string()],
+        )
+        .with_state(AppState::new(AppConfig::default()));
+        let response = any
+            .oneshot(request(Method::GET, "https://anyone.example"))
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    }
+
+    #[tokio::test]
+    async fn test_handler_plus_as_space() {
+        BANG_CACHE.write().insert(
+            "plustest".to_string(),
+            Bang::new("plustest", "https://example.com/?q={{{s}}}"),
+        );
+
+        let response = handler(
+            RawQuery(Some("q=!plustest+a+b%2Bc".to_string())),
+            PreferredLanguage(None),
+            State(AppState::new(AppConfig::default())),
+        )
+        .await;
+        assert_eq!(response.headers()[BANG_HEADER], "plustest");
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://example.com/?q=a%20b%2Bc"
+        );
+
+        let config = AppConfig {
+            plus_as_space: false,
+            ..AppConfig::default()
+        };
+        let response = handler(
+            RawQuery(Some("q=!plustest%20a+b".to_string())),
+            PreferredLanguage(None),
+            State(AppState::new(config)),
+        )
+        .await;
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://example.com/?q=a%2Bb"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handler_query_param() {
+        BANG_CACHE.write().insert(
+            "paramtest".to_string(),
+            Bang::new("paramtest", "https://example.com/?q={{{s}}}"),
+        );
+        let app_state = AppState::new(AppConfig {
+            query_param: "search".to_string(),
+            ..AppConfig::default()
+        });
+
+        for (raw_query, expected) in [
+            ("search=!paramtest+rust", "https://example.com/?q=rust"),
+            ("q=!paramtest+go", "https://example.com/?q=go"),
+            (
+                "q=!paramtest+go&search=!paramtest+rust",
+                "https://example.com/?q=rust",
+            ),
+        ] {
+            let response = handler(
+                RawQuery(Some(raw_query.to_string())),
+                PreferredLanguage(None),
+                State(app_state.clone()),
+            )
+            .await;
+            assert_eq!(
+                response.headers()[header::LOCATION],
+                expected,
+                "{raw_query}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_preferred_language() {
+        assert_eq!(
+            preferred_language("de-AT,de;q=0.9,en;q=0.8").as_deref(),
+            Some("de-AT")
+        );
+        assert_eq!(
+            preferred_language("en;q=0.5, fr-CH , *;q=1").as_deref(),
+            Some("fr-CH")
+        );
+        assert_eq!(preferred_language("*, en;q=0").as_deref(), None);
+        assert_eq!(preferred_language("").as_deref(), None);
+    }
+
+    #[tokio::test]
+    async fn test_handler_default_search_by_lang() {
+        let config = AppConfig {
+            default_search_by_lang: std::collections::HashMap::from([(
+                "de".to_string(),
+                "https://search.example.de/?q={}".to_string(),
+            )]),
+            ..AppConfig::default()
+        };
+        let response = handler(
+            RawQuery(Some("q=langtest".to_string())),
+            PreferredLanguage(Some("de-AT".to_string())),
+            State(AppState::new(config.clone())),
+        )
+        .await;
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://search.example.de/?q=langtest"
+        );
+
+        let response = handler(
+            RawQuery(Some("q=langtest".to_string())),
+            PreferredLanguage(Some("en-US".to_string())),
+            State(AppState::new(config)),
+        )
+        .await;
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://www.qwant.com/?q=langtest"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handler_confirm_bang() {
+        let mut bang = Bang::new("confirmtest", "https://sketchy.example/?q={{{s}}}");
+        bang.confirm = Some(true);
+        BANG_CACHE.write().insert("confirmtest".to_string(), bang);
+        BANG_CACHE.write().insert(
+            "noconfirmtest".to_string(),
+            Bang::new("noconfirmtest", "https://example.com/?q={{{s}}}"),
+        );
+        let app_state = AppState::new(AppConfig::default());
+
+        let response = handler(
+            RawQuery(Some("q=!confirmtest+a%26b".to_string())),
+            PreferredLanguage(None),
+            State(app_state.clone()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::LOCATION).is_none());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            confirm_page("https://sketchy.example/?q=a%26b")
+        );
+        assert!(
+            confirm_page("https://sketchy.example/?q=a&b=\"c\"")
+                .contains("<a href=\"https://sketchy.example/?q=a&amp;b=&quot;c&quot;\"")
+        );
+
+        let response = handler(
+            RawQuery(Some("q=!noconfirmtest+a".to_string())),
+            PreferredLanguage(None),
+            State(app_state),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://example.com/?q=a"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handler_post_bang() {
+        let mut bang = Bang::new("posttest", "https://scholar.example/search");
+        bang.method = Some(BangMethod::Post);
+        bang.body_template = Some("query={{{s}}}&type=all".to_string());
+        BANG_CACHE.write().insert("posttest".to_string(), bang);
+
+        let response = handler(
+            RawQuery(Some("q=!posttest+a+%22b%22".to_string())),
+            PreferredLanguage(None),
+            State(AppState::new(AppConfig::default())),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[BANG_HEADER], "posttest");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.contains("onload=\"document.forms[0].submit()\""));
+        assert!(html.contains(
+            "<form method=\"post\" action=\"https://scholar.example/search\">\
+             <input type=\"hidden\" name=\"query\" value=\"a &quot;b&quot;\">\
+             <input type=\"hidden\" name=\"type\" value=\"all\">"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_list_bangs_filter() {
+        BANG_CACHE.write().extend(
+            [
+                ("filtertestalpha", Some(Category::Tech)),
+                ("filtertestbeta", Some(Category::News)),
+                ("filtertestgamma", None),
+            ]
+            .map(|(trigger, category)| {
+                let bang = Bang {
+                    category,
+                    ..Bang::new(trigger, "https://example.com/?q={{{s}}}")
+                };
+                (trigger.to_string(), bang)
+            }),
+        );
+        let app = build_router(AppState::new(AppConfig::default()));
+        let rows = async |uri: &str| {
+            let response = app
+                .clone()
+                .oneshot(Request::get(uri).body(axum::body::Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{uri}");
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let html = String::from_utf8(body.to_vec()).unwrap();
+            let mut triggers: Vec<String> = html
+                .split("<td><strong>")
+                .skip(1)
+                .map(|row| row[..row.find('<').unwrap()].to_string())
+                .filter(|trigger| trigger.starts_with("filtertest"))
+                .collect();
+            triggers.sort();
+            (html, triggers)
+        };
+
+        // Submitting the filter searches every page, not only the one shown.
+        let mut found = Vec::new();
+        for page in 1..=3 {
+            let (_, triggers) = rows(&format!("/bangs?filter=FilterTest&per=1&page={page}")).await;
+            found.extend(triggers);
+        }
+        found.sort();
+        assert_eq!(
+            found,
+            ["filtertestalpha", "filtertestbeta", "filtertestgamma"]
+        );
+        let (html, _) = rows("/bangs?filter=FilterTest&per=1&page=2").await;
+        assert!(html.contains(r#"name="filter" value="FilterTest""#));
+        assert!(
+            html.contains(r#"<a href="/bangs?page=3&amp;per=1&amp;filter=FilterTest" rel="next">"#)
+        );
+        // The rows shown are filtered in the page as well, without a request.
+        assert!(html.contains(FILTER_SCRIPT));
+        for category in Category::ALL {
+            assert!(html.contains(&format!(
+                r#"<button type="button" data-category="{category}">"#
+            )));
+        }
+
+        let (html, triggers) = rows("/bangs?filter=filtertest&category=Tech").await;
+        assert_eq!(triggers, ["filtertestalpha"]);
+        assert!(
+            html.contains(r#"<tr class="bang" data-category="Tech"><td><strong>filtertestalpha<"#)
+        );
+        assert!(html.contains(r#"<button type="button" class="active" data-category="Tech">"#));
+        assert!(
+            html.contains(r#"<input id="category" type="hidden" name="category" value="Tech">"#)
+        );
+
+        // A page or page size that is not a number falls back to the default.
+        let (_, triggers) = rows("/bangs?filter=filtertest&page=last&per=all").await;
+        assert_eq!(
+            triggers,
+            ["filtertestalpha", "filtertestbeta", "filtertestgamma"]
+        );
+    }
+
+    #[test]
+    fn test_list_bangs_grouping() {
+        let mut cache = BangMap::default();
+        for (trigger, category, subcategory, relevance) in [
+            ("grouplow", Some(Category::Tech), Some("Programming"), 1),
+            ("grouphigh", Some(Category::Tech), Some("Programming"), 10),
+            ("groupnone", None, None, 5),
+        ] {
+            let bang = Bang {
+                category,
+                subcategory: subcategory.map(str::to_string),
+                relevance: Some(relevance),
+                ..Bang::new(trigger, "https://example.com/?q=")
+            };
+            cache.insert(trigger.to_string(), bang);
+        }
+
+        let mut html = String::new();
+        write_active_bangs(&mut html, &sorted_active_bangs(&cache));
+        let tech = html.find("<summary>Tech (2)</summary>").unwrap();
+        let uncategorized = html.find("<summary>Uncategorized (1)</summary>").unwrap();
+        assert!(tech < uncategorized);
+        assert!(html.contains("<h3>Programming</h3>"));
+        assert!(html.find("grouphigh").unwrap() < html.find("grouplow").unwrap());
+    }
+
+    #[test]
+    fn test_list_bangs_relevance_order() {
+        let mut cache = BangMap::default();
+        for (trigger, category, relevance) in [
+            ("orderb", Some(Category::Tech), Some(50)),
+            ("ordernone", Some(Category::Tech), None),
+            ("orderlow", Some(Category::Tech), Some(1)),
+            ("ordertop", Some(Category::Tech), Some(100)),
+            ("ordera", Some(Category::Tech), Some(50)),
+            ("newslow", Some(Category::News), Some(5)),
+            ("newstop", Some(Category::News), Some(20)),
+            ("uncategorized", None, Some(1000)),
+        ] {
+            let bang = Bang {
+                category,
+                relevance,
+                ..Bang::new(trigger, "https://example.com/?q=")
+            };
+            cache.insert(trigger.to_string(), bang);
+        }
+
+        let mut html = String::new();
+        write_active_bangs(&mut html, &sorted_active_bangs(&cache));
+        let rows: Vec<&str> = html
+            .split("<td><strong>")
+            .skip(1)
+            .map(|row| &row[..row.find('<').unwrap()])
+            .collect();
+        // The most relevant bang of each category comes first, but categories keep their order
+        // however relevant their bangs are.
+        assert_eq!(
+            rows,
+            [
+                "newstop",
+                "newslow",
+                "ordertop",
+                "ordera",
+                "orderb",
+                "orderlow",
+                "ordernone",
+                "uncategorized"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bangs_json() {
+        BANG_CACHE.write().insert(
+            "jsontest".to_string(),
+            Bang::new("jsontest", "https://example.com/?q={{{s}}}"),
+        );
+        let response = build_router(AppState::new(AppConfig::default()))
+            .oneshot(
+                Request::get("/bangs.json")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let bangs: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        assert!(bangs.contains(&serde_json::json!({
+            "trigger": "jsontest",
+            "url_template": "https://example.com/?q={{{s}}}",
+        })));
+
+        // Caches filled in a different order still serialize to the same bytes.
+        let json = |triggers: &[&str]| {
+            let mut cache = BangMap::default();
+            for trigger in triggers {
+                let bang = Bang {
+                    relevance: Some(u64::from(trigger.as_bytes()[0] % 2)),
+                    ..Bang::new(*trigger, "https://example.com/?q=")
+                };
+                cache.insert((*trigger).to_string(), bang);
+            }
+            serde_json::to_vec(&sorted_active_bangs(&cache)).unwrap()
+        };
+        assert_eq!(
+            json(&["a", "c", "e", "g", "b", "d", "f", "h"]),
+            json(&["g", "e", "c", "a", "h", "f", "d", "b"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_bangs_pagination() {
+        BANG_CACHE.write().extend((0..15).map(|i| {
+            let trigger = format!("pagetest{i}");
+            (
+                trigger.clone(),
+                Bang::new(trigger, "https://example.com/?q={{{s}}}"),
+            )
+        }));
+        let page = |page| async move {
+            let Html(html) = list_bangs(
+                Query(BangsParams {
+                    theme: Some("dark".to_string()),
+                    page,
+                    per: Some("10".to_string()),
+                    ..BangsParams::default()
+                }),
+                State(AppState::new(AppConfig::default())),
+            )
+            .await;
+            html
+        };
+
+        let html = page(None).await;
+        let (_, active) = html.split_once("<h2>Active Bangs</h2>").unwrap();
+        assert_eq!(active.matches(r#"<tr class="bang""#).count(), 10);
+        assert!(
+            active.contains(r#"<a href="/bangs?page=2&amp;per=10&amp;theme=dark" rel="next">"#)
+        );
+        assert!(!active.contains(r#"rel="prev""#));
+
+        let html = page(Some("2".to_string())).await;
+        assert!(html.contains(r#"<a href="/bangs?page=1&amp;per=10&amp;theme=dark" rel="prev">"#));
+    }
+
+    #[tokio::test]
+    async fn test_list_bangs_escapes_html() {
+        BANG_CACHE.write().insert(
+            "<script>escapetest".to_string(),
+            Bang {
+                subcategory: Some("<b>".to_string()),
+                ..Bang::new(
+                    "<script>escapetest",
+                    "https://example.com/?q={{{s}}}&a=\"b\"",
+                )
+            },
+        );
+        let app_state = AppState::new(AppConfig {
+            bangs: Some(vec![Bang {
+                short_name: Some("</td>".to_string()),
+                ..Bang::new("<script>escapeconf", "https://example.com/?q=")
+            }]),
+            ..AppConfig::default()
+        });
+
+        for plain in [None, Some("1".to_string())] {
+            let Html(html) = list_bangs(
+                Query(BangsParams {
+                    plain,
+                    per: Some(MAX_BANGS_PER_PAGE.to_string()),
+                    ..BangsParams::default()
+                }),
+                State(app_state.clone()),
+            )
+            .await;
+            assert!(html.contains("&lt;script&gt;escapetest"));
+            assert!(html.contains("{{{s}}}&amp;a=&quot;b&quot;"));
+            assert!(!html.contains("<script>escape"));
+            assert!(!html.contains("<b>"));
+            assert!(!html.contains("\"</td>"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_bangs_description() {
+        let bangs: FileConfig = toml::from_str(
+            r#"
+            [[bangs]]
+            trigger = "describedtest"
+            url_template = "https://example.com/?q={{{s}}}"
+            description = "Searches <example>"
+            "#,
+        )
+        .unwrap();
+        let bangs = bangs.bangs.unwrap();
+        assert!(
+            toml::to_string(&bangs[0])
+                .unwrap()
+                .contains(r#"description = "Searches <example>""#)
+        );
+        let app_state = AppState::new(AppConfig {
+            bangs: Some(bangs),
+            ..AppConfig::default()
+        });
+
+        let Html(html) = list_bangs(Query(BangsParams::default()), State(app_state)).await;
+        assert!(html.contains("<th>Description</th>"));
+        assert!(html.contains(
+            "<td>describedtest</td><td>https://example.com/?q={{{s}}}</td><td>Searches &lt;example&gt;</td>"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_list_bangs_theme() {
+        let app_state = AppState::new(AppConfig::default());
+
+        let Html(html) = list_bangs(
+            Query(BangsParams {
+                theme: Some("light".to_string()),
+                ..BangsParams::default()
+            }),
+            State(app_state.clone()),
+        )
+        .await;
+        assert!(html.contains(r#"<html data-theme="light">"#));
+        assert!(html.contains(r#"id="theme-toggle""#));
+
+        let Html(html) = list_bangs(
+            Query(BangsParams {
+                theme: Some("<script>".to_string()),
+                ..BangsParams::default()
+            }),
+            State(app_state),
+        )
+        .await;
+        assert!(html.contains("<html>"));
+    }
+
+    #[tokio::test]
+    async fn test_list_bangs_plain() {
+        BANG_CACHE.write().insert(
+            "plainview".to_string(),
+            Bang::new("plainview", "https://example.com/?q={{{s}}}"),
+        );
+
+        let Html(html) = list_bangs(
+            Query(BangsParams {
+                plain: Some("1".to_string()),
+                ..BangsParams::default()
+            }),
+            State(AppState::new(AppConfig::default())),
+        )
+        .await;
+        assert!(html.starts_with("<table>"));
+        assert!(html.contains("<td>plainview</td>"));
+        assert!(!html.contains("<style>"));
+        assert!(!html.contains("<script>"));
+    }
+
+    #[tokio::test]
+    async fn test_add_bang_rejects_invalid_template() {
+        let app_state = AppState::new(AppConfig {
+            bangs: Some(Vec::new()),
+            ..AppConfig::default()
+        });
+        let bang = Bang {
+            trigger: "invalid".to_string(),
+            url_template: "example.com/search?q={{{s}}}".to_string(),
+            ..Bang::default()
+        };
+
+        let response = add_bang(
+            State(app_state.clone()),
+            Query(AddBangParams { overwrite: None }),
+            BangPayload(bang),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(app_state.get_config().bangs.unwrap().is_empty());
+        assert!(!BANG_CACHE.read().contains_key("invalid"));
+    }
+
+    #[tokio::test]
+    async fn test_admin_token() {
+        let app = |admin_token: Option<&str>| {
+            let app_state = AppState::new(AppConfig {
+                admin_token: admin_token.map(str::to_string),
+                ..AppConfig::default()
+            });
+            admin_routes(&app_state).with_state(app_state)
+        };
+        // The template is invalid, so requests that get through are rejected by `add_bang`
+        // without touching the configuration file.
+        let request = |header: Option<(&'static str, &str)>| {
+            let mut request = add_bang_request("admintest", "example.com/?q={{{s}}}", "");
+            if let Some((name, value)) = header {
+                request
+                    .headers_mut()
+                    .insert(name, HeaderValue::from_str(value).unwrap());
+            }
+            request
+        };
+
+        let response = app(None).oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let guarded = app(Some("s3cret"));
+        for header in [
+            None,
+            Some(("authorization", "Bearer wrong")),
+            Some(("authorization", "s3cret")),
+            Some((ADMIN_TOKEN_HEADER, "s3cre")),
+        ] {
+            let response = guarded.clone().oneshot(request(header)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{header:?}");
+        }
+        let response = guarded
+            .clone()
+            .oneshot(
+                Request::post("/reload_bangs")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        for header in [
+            ("authorization", "Bearer s3cret"),
+            (ADMIN_TOKEN_HEADER, "s3cret"),
+        ] {
+            let response = guarded
+                .clone()
+                .oneshot(request(Some(header)))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{header:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_bang_max_user_bangs() {
+        let bangs = (0..2)
+            .map(|i| Bang::new(format!("limit{i}"), "https://example.com/?q={{{s}}}"))
+            .collect();
+        let app_state = AppState::new(AppConfig {
+            max_user_bangs: 2,
+            bangs: Some(bangs),
+            ..AppConfig::default()
+        });
+
+        let response = add_bang(
+            State(app_state.clone()),
+            Query(AddBangParams { overwrite: None }),
+            BangPayload(Bang::new("limittest", "https://example.com/?q={{{s}}}")),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(app_state.get_config().bangs.unwrap().len(), 2);
+        assert!(!BANG_CACHE.read().contains_key("limittest"));
+    }
+
+    #[tokio::test]
+    async fn test_add_bang_json_body() {
+        let (_dir, config_path) = config_file();
+        let app_state = AppState::new(AppConfig {
+            bangs: Some(Vec::new()),
+            ..AppConfig::default()
+        })
+        .with_config_path(&config_path);
+        let app = Router::new()
+            .route("/add_bang", post(add_bang))
+            .with_state(app_state.clone());
+        let request = Request::post("/add_bang")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(axum::body::Body::from(
+                r#"{"trigger": "jsontest", "url_template": "https://example.com/?q={{{s}}}", "category": "Tech"}"#,
+            ))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            BANG_CACHE
+                .read()
+                .get("jsontest")
+                .map(|bang| bang.url_template.as_str()),
+            Some("https://example.com/?q={{{s}}}")
+        );
+        assert_eq!(app_state.get_config().bangs.unwrap().len(), 1);
+        let contents = std::fs::read_to_string(&config_path).unwrap();
+        assert!(contents.contains(r#"trigger = "jsontest""#));
+        assert!(contents.contains(r#"category = "Tech""#));
+    }
+
+    #[tokio::test]
+    async fn test_append_file_config_round_trip() {
+        let (_dir, config_path) = config_file();
+
+        let bang = Bang {
+            category: Some(Category::OnlineServices),
+            domain: Some("round.example".to_string()),
+            relevance: Some(42),
+            short_name: Some(r#"Round "Trip" \ Test"#.to_string()),
+            subcategory: Some("Tests".to_string()),
+            trigger: "roundtrip".to_string(),
+            url_template: "https://round.example/?q={{{s}}}&x=\"y\"".to_string(),
+            ignore_term: Some(false),
+            decode_slashes: Some(false),
+            space_as_plus: Some(true),
+            double_encode: Some(true),
+            method: Some(BangMethod::Post),
+            body_template: Some("q={{{s}}}&lang=en".to_string()),
+            confirm: Some(true),
+            no_log: Some(true),
+            ..Bang::default()
+        };
+        append_file_config(&config_path, bang.clone());
+
+        let reloaded: Vec<_> = get_file_config(&config_path)
+            .unwrap()
+            .bangs
+            .unwrap()
+            .into_iter()
+            .filter(|reloaded| reloaded.trigger == "roundtrip")
+            .collect();
+        assert_eq!(reloaded, [bang]);
+    }
+
+    #[tokio::test]
+    async fn test_add_bang_duplicate_trigger() {
+        let (_dir, config_path) = config_file();
+        let app_state = AppState::new(AppConfig {
+            bangs: Some(Vec::new()),
+            ..AppConfig::default()
+        })
+        .with_config_path(&config_path);
+        let app = Router::new()
+            .route("/add_bang", post(add_bang))
+            .with_state(app_state.clone());
+
+        // A brand-new trigger is accepted.
+        let response = app
+            .clone()
+            .oneshot(add_bang_request("duptest", "https://one.example/?q=", ""))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // The same trigger again conflicts and changes nothing.
+        let response = app
+            .clone()
+            .oneshot(add_bang_request("duptest", "https://two.example/?q=", ""))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(
+            BANG_CACHE
+                .read()
+                .get("duptest")
+                .map(|bang| bang.url_template.as_str()),
+            Some("https://one.example/?q=")
+        );
+
+        // With overwrite the entry is replaced everywhere.
+        let response = app
+            .oneshot(add_bang_request(
+                "duptest",
+                "https://two.example/?q=",
+                "?overwrite=true",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bangs = app_state.get_config().bangs.unwrap();
+        assert_eq!(bangs.len(), 1);
+        assert_eq!(bangs[0].url_template, "https://two.example/?q=");
+        assert_eq!(
+            BANG_CACHE
+                .read()
+                .get("duptest")
+                .map(|bang| bang.url_template.as_str()),
+            Some("https://two.example/?q=")
+        );
+        let file_config = get_file_config(&config_path).unwrap();
+        let file_bangs: Vec<_> = file_config
+            .bangs
+            .unwrap()
+            .into_iter()
+            .filter(|bang| bang.trigger == "duptest")
+            .collect();
+        assert_eq!(file_bangs.len(), 1);
+        assert_eq!(file_bangs[0].url_template, "https://two.example/?q=");
+    }
+
+    #[tokio::test]
+    async fn test_batch_resolve() {
+        BANG_CACHE.write().insert(
+            "batchtest".to_string(),
+            Bang::new("batchtest", "https://example.com/?q="),
+        );
+        let app = Router::new()
+            .route("/api/batch_resolve", post(batch_resolve))
+            .with_state(AppState::new(AppConfig::default()));
+        let request = Request::post("/api/batch_resolve")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(axum::body::Body::from(
+                r#"["!batchtest rust", "rust programming", "!unknownbatchtest rust"]"#,
+            ))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let outcomes: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let default_search = AppConfig::default().default_search;
+        assert_eq!(
+            outcomes,
+            serde_json::json!([
+                { "url": "https://example.com/?q=rust", "kind": "bang_matched", "trigger": "batchtest", "source": "remote" },
+                { "url": default_search.replace("{}", "rust%20programming"), "kind": "default_search", "source": "default" },
+                { "url": default_search.replace("{}", "%21unknownbatchtest%20rust"), "kind": "default_bang", "source": "default" },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reload_bangs_only() {
+        let (_dir, config_path) = config_file();
+
+        let old = Bang::new("reloadold", "https://old.example/?q=");
+        BANG_CACHE.write().insert(old.trigger.clone(), old.clone());
+        let app_state = AppState::new(AppConfig {
+            // Unreachable, so a fetch would fail the reload.
+            bangs_url: vec!["http://127.0.0.1:9/bang.js".to_string()],
+            bangs: Some(vec![old]),
+            ..AppConfig::default()
+        })
+        .with_config_path(&config_path);
+        std::fs::write(
+            &config_path,
+            "[[bangs]]\ntrigger = \"reloadnew\"\nurl_template = \"https://new.example/?q=\"\n",
+        )
+        .unwrap();
+
+        let app = Router::new()
+            .route("/reload_bangs", post(reload_bangs))
+            .with_state(app_state.clone());
+        let response = app
+            .oneshot(
+                Request::post("/reload_bangs")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let cache = BANG_CACHE.read();
+        assert!(!cache.contains_key("reloadold"));
+        assert_eq!(
+            cache
+                .get("reloadnew")
+                .map(|bang| bang.url_template.as_str()),
+            Some("https://new.example/?q=")
+        );
+        drop(cache);
+        let bangs = app_state.get_config().bangs.unwrap();
+        assert_eq!(bangs.len(), 1);
+        assert_eq!(bangs[0].trigger, "reloadnew");
+    }
+
+    #[tokio::test]
+    async fn test_favicon() {
+        let response = build_router(AppState::new(AppConfig::default()))
+            .oneshot(
+                Request::get("/favicon.ico")
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            STATIC_CACHE_CONTROL
+        );
+    }
+
+    #[tokio::test]
+    async fn test_version() {
+        let app_state = AppState::new(AppConfig {
+            admin_token: Some("versionsecret".to_string()),
+            default_search: "https://search.example/?key=versionsecret&q={}".to_string(),
+            bangs: Some(vec![Bang::new("versionbang", "https://example.com/?q=")]),
+            ..AppConfig::default()
+        });
+
+        let Json(body) = version(State(app_state)).await;
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert!(body["git_commit"].is_string());
+        assert!(body["build_timestamp"].as_u64().is_some());
+        assert_eq!(body["config"]["admin_token_set"], true);
+        assert_eq!(body["config"]["bangs"], 1);
+        assert_eq!(body["config"]["port"], 3000);
+        assert!(!body.to_string().contains("versionsecret"));
+        assert!(!body.to_string().contains("example.com"));
+        assert!(body["config"].get("default_search").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_health_endpoints() {
+        let app = Router::new()
+            .route("/healthz", get(healthz))
+            .route("/readyz", get(readyz));
+        BANG_CACHE.write().insert(
+            "readytest".to_string(),
+            Bang::new("readytest", "https://example.com/?q="),
+        );
+
+        for uri in ["/healthz", "/readyz"] {
+            let response = app
+                .clone()
+                .oneshot(Request::get(uri).body(axum::body::Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_suggestions_without_placeholder() {
+        let config = AppConfig {
+            // Unreachable, so the test fails if upstream is queried.
+            search_suggestions: "http://127.0.0.1:9/suggest".to_string(),
+            ..AppConfig::default()
+        };
+        assert_eq!(config.validate().len(), 1);
+
+        let response = suggestions_proxy(
+            RawQuery(Some(format!("q={}", urlencoding::encode("rust")))),
+            State(AppState::new(config)),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], br#"["rust",[]]"#);
+    }
+
+    /// Serve `body` for every request on a local port, sending the raw query of each request
+    /// through `queries`, and return a `search_suggestions` template for it.
+    async fn suggestions_upstream(
+        body: String,
+    ) -> (String, tokio::sync::mpsc::UnboundedReceiver<Option<String>>) {
+        let (sender, queries) = tokio::sync::mpsc::unbounded_channel();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route(
+            "/suggest",
+            get(move |RawQuery(query): RawQuery| {
+                sender.send(query).unwrap();
+                async move { body }
+            }),
+        );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{addr}/suggest?q={{}}"), queries)
+    }
+
+    async fn suggestions_body(search_suggestions: String, query: &str) -> serde_json::Value {
+        let app_state = AppState::new(AppConfig {
+            search_suggestions,
+            suggestions_max_bytes: 1024,
+            ..AppConfig::default()
+        });
+        let response = suggestions_proxy(
+            RawQuery(Some(format!("q={}", urlencoding::encode(query)))),
+            State(app_state),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            SUGGESTIONS_CONTENT_TYPE
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[test]
+    fn test_suggestions_url() {
+        let template = "https://suggest.example/api?q={}&client=opensearch";
+        assert_eq!(
+            suggestions_url(template, "rust"),
+            "https://suggest.example/api?q=rust&client=opensearch"
+        );
+        assert_eq!(
+            suggestions_url(template, "a&b=c #d"),
+            "https://suggest.example/api?q=a%26b%3Dc%20%23d&client=opensearch"
+        );
+        assert_eq!(
+            suggestions_url(template, "c++ 1/2 ?x é"),
+            "https://suggest.example/api?q=c%2B%2B%201%2F2%20%3Fx%20%C3%A9&client=opensearch"
+        );
+    }
+
+    #[test]
+    fn test_normalize_suggestions() {
+        let expected = vec![
+            "rust".to_string(),
+            "rust game".to_string(),
+            "rustlings".to_string(),
+        ];
+        let recorded = [
+            (
+                SuggestionProvider::Brave,
+                r#"["rust",["rust","rust game","rustlings"]]"#,
+            ),
+            (
+                SuggestionProvider::Custom("https://suggest.example/?q={}".to_string()),
+                r#"["rust",["rust","rust game","rustlings"],["","",""],["","",""]]"#,
+            ),
+            (
+                SuggestionProvider::Google,
+                r#"["rust",["rust","rust game","rustlings"],["","",""],[],{"google:clientdata":{"bpc":false,"tlw":false},"google:suggestrelevance":[1250,601,600],"google:suggesttype":["QUERY","QUERY","QUERY"],"google:verbatimrelevance":1300}]"#,
+            ),
+            (
+                SuggestionProvider::DuckDuckGo,
+                r#"[{"phrase":"rust"},{"phrase":"rust game"},{"phrase":"rustlings"}]"#,
+            ),
+        ];
+        for (provider, body) in recorded {
+            let json = serde_json::from_str(body).unwrap();
+            assert_eq!(
+                normalize_suggestions(&provider, json),
+                Some(expected.clone()),
+                "{provider:?}"
+            );
+        }
+
+        // Each provider's parser rejects the layouts of the others.
+        let google = serde_json::json!(["rust", ["rust"], [""], [], {}]);
+        let duckduckgo = serde_json::json!([{ "phrase": "rust" }]);
+        let opensearch = serde_json::json!(["rust", expected]);
+        assert_eq!(
+            normalize_suggestions(&SuggestionProvider::Brave, google),
+            None
+        );
+        assert_eq!(
+            normalize_suggestions(&SuggestionProvider::Google, duckduckgo),
+            None
+        );
+        assert_eq!(
+            normalize_suggestions(&SuggestionProvider::DuckDuckGo, opensearch),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_suggestions_encode_query() {
+        let upstream = r#"["a&b=c #d",["a&b=c #d e"]]"#.to_string();
+        let (template, mut queries) = suggestions_upstream(upstream.clone()).await;
+        assert_eq!(
+            suggestions_body(template, "a&b=c #d").await,
+            serde_json::from_str::<serde_json::Value>(&upstream).unwrap()
+        );
+        assert_eq!(
+            queries.recv().await.unwrap().as_deref(),
+            Some("q=a%26b%3Dc%20%23d")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_suggestions_opensearch_form() {
+        // Descriptions and URLs are dropped, leaving the two-element form.
+        let upstream = r#"["rust",["rust game","rustlings"],["A game","Exercises"],["https://a.example","https://b.example"]]"#;
+        let (template, _queries) = suggestions_upstream(upstream.to_string()).await;
+        assert_eq!(
+            suggestions_body(template, "rust").await,
+            serde_json::json!(["rust", ["rust game", "rustlings"]])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_suggestions_reject_invalid_upstream() {
+        let oversized = serde_json::json!(["rust", vec!["rust".repeat(100); 10]]).to_string();
+        for body in [
+            oversized,
+            r#"{"rust": []}"#.to_string(),
+            "[\"rust\", [1]]".to_string(),
+        ] {
+            let (template, _queries) = suggestions_upstream(body.clone()).await;
+            assert_eq!(
+                suggestions_body(template, "rust").await,
+                serde_json::json!(["rust", []]),
+                "{body}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_suggestions_metrics() {
+        async fn scrape(name: &str) -> u64 {
+            let response = build_router(AppState::new(AppConfig::default()))
+                .oneshot(
+                    Request::get("/metrics")
+                        .body(axum::body::Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body = String::from_utf8(body.to_vec()).unwrap();
+            body.lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+                .unwrap()
+                .parse()
+                .unwrap()
+        }
+        let requests = scrape("redirector_suggestion_requests_total").await;
+        let errors = scrape("redirector_suggestion_upstream_errors_total").await;
+        let fetches = scrape("redirector_suggestion_upstream_duration_seconds_count").await;
+
+        let (template, _queries) = suggestions_upstream(r#"{"rust": []}"#.to_string()).await;
+        suggestions_body(template, "rust").await;
+
+        // Other tests fetch suggestions concurrently, so only check that the counters went up.
+        assert!(scrape("redirector_suggestion_requests_total").await > requests);
+        assert!(scrape("redirector_suggestion_upstream_errors_total").await > errors);
+        assert!(scrape("redirector_suggestion_upstream_duration_seconds_count").await > fetches);
+    }
+
+    #[tokio::test]
+    async fn test_suggestions_disabled() {
+        let (template, mut queries) =
+            suggestions_upstream(r#"["rust", ["rustup"]]"#.to_string()).await;
+        let app_state = AppState::new(AppConfig {
+            search_suggestions: template,
+            enable_suggestions: false,
+            ..AppConfig::default()
+        });
+        let response = suggestions_proxy(
+            RawQuery(Some(format!("q={}", urlencoding::encode("rust")))),
+            State(app_state.clone()),
+        )
+        .await
+        .into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, serde_json::json!(["rust", []]));
+        assert!(queries.try_recv().is_err());
+
+        let response = opensearch(Origin("http://0.0.0.0:3000".to_string()), State(app_state))
+            .await
+            .into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let xml = String::from_utf8(body.to_vec()).unwrap();
+        assert!(xml.contains("/?q={searchTerms}"));
+        assert!(!xml.contains("suggest"));
+    }
+
+    /// Check that `xml` is well-formed: one root element, every element closed in order and
+    /// every attribute value quoted.
+    fn assert_well_formed_xml(xml: &str) {
+        let body = xml
+            .strip_prefix(r#"<?xml version="1.0" encoding="UTF-8"?>"#)
+            .unwrap();
+        let mut open = Vec::new();
+        let mut roots = 0;
+        let mut rest = body;
+        while let Some(start) = rest.find('<') {
+            assert!(!rest[..start].contains('>'), "stray '>' in {rest}");
+            let end = start + rest[start..].find('>').unwrap();
+            let tag = &rest[start + 1..end];
+            rest = &rest[end + 1..];
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(name.trim()), "mismatched </{name}>");
+                continue;
+            }
+            let self_closing = tag.ends_with('/');
+            let tag = tag.trim_end_matches('/');
+            let mut parts = tag.splitn(2, char::is_whitespace);
+            let name = parts.next().unwrap();
+            let mut attributes = parts.next().unwrap_or_default().trim();
+            while !attributes.is_empty() {
+                let (_, value) = attributes.split_once("=\"").unwrap();
+                let (_, next) = value.split_once('"').unwrap();
+                attributes = next.trim();
+            }
+            if open.is_empty() {
+                roots += 1;
+            }
+            if !self_closing {
+                open.push(name);
+            }
+        }
+        assert!(open.is_empty(), "unclosed {open:?}");
+        assert_eq!(roots, 1);
+    }
+
+    #[tokio::test]
+    async fn test_opensearch_descriptor() {
+        let app_state = AppState::new(AppConfig {
+            query_param: "query".to_string(),
+            base_path: "/search".to_string(),
+            ..AppConfig::default()
+        });
+        let response = opensearch(Origin("http://0.0.0.0:3000".to_string()), State(app_state))
+            .await
+            .into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let xml = String::from_utf8(body.to_vec()).unwrap();
+        assert_well_formed_xml(&xml);
+        assert!(xml.contains(
+            r#"<Url type="text/html" method="GET" template="http://0.0.0.0:3000/search/?query={searchTerms}" />"#
+        ));
+        assert!(xml.contains(
+            r#"<Url type="application/x-suggestions+json" method="GET" template="http://0.0.0.0:3000/search/suggest?query={searchTerms}" />"#
+        ));
+        assert!(xml.contains("<moz:SearchForm>http://0.0.0.0:3000/search/bangs</moz:SearchForm>"));
+
+        let (template, mut queries) = suggestions_upstream(r#"["rust", []]"#.to_string()).await;
+        let app_state = AppState::new(AppConfig {
+            query_param: "query".to_string(),
+            search_suggestions: template,
+            ..AppConfig::default()
+        });
+        let response =
+            suggestions_proxy(RawQuery(Some("query=rust".to_string())), State(app_state))
+                .await
+                .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(queries.recv().await.unwrap().as_deref(), Some("q=rust"));
+    }
+
+    #[tokio::test]
+    async fn test_opensearch_origin() {
+        let app = build_router(AppState::new(AppConfig {
+            trusted_proxies: vec!["127.0.0.1/32".parse().unwrap()],
+            ..AppConfig::default()
+        }));
+        let template = async |peer: [u8; 4], headers: &[(&str, &str)]| {
+            let mut request = Request::get("/opensearch.xml");
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            let mut request = request.body(axum::body::Body::empty()).unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from((peer, 4711))));
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert!(
+                response.headers()[header::VARY]
+                    .to_str()
+                    .unwrap()
+                    .contains("Host")
+            );
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let xml = String::from_utf8(body.to_vec()).unwrap();
+            let start = xml.find(r#"template=""#).unwrap() + r#"template=""#.len();
+            xml[start..start + xml[start..].find('"').unwrap()].to_string()
+        };
+
+        let local = [127, 0, 0, 1];
+        let remote = [192, 0, 2, 1];
+        assert_eq!(
+            template(local, &[]).await,
+            "http://0.0.0.0:3000/?q={searchTerms}"
+        );
+        assert_eq!(
+            template(remote, &[("host", "search.example.com")]).await,
+            "http://search.example.com/?q={searchTerms}"
+        );
+        assert_eq!(
+            template(
+                local,
+                &[
+                    ("host", "127.0.0.1:3000"),
+                    ("x-forwarded-proto", "https"),
+                    ("x-forwarded-host", "search.example.com")
+                ]
+            )
+            .await,
+            "https://search.example.com/?q={searchTerms}"
+        );
+        assert_eq!(
+            template(
+                local,
+                &[(
+                    "forwarded",
+                    "for=192.0.2.1;proto=http, for=127.0.0.1;proto=https;host=\"search.example.com:8443\""
+                )]
+            )
+            .await,
+            "https://search.example.com:8443/?q={searchTerms}"
+        );
+        // Only the values added by the nearest proxy count, the client wrote those before them.
+        assert_eq!(
+            template(
+                local,
+                &[(
+                    "forwarded",
+                    "for=192.0.2.1;proto=https, for=127.0.0.1;proto=http"
+                )]
+            )
+            .await,
+            "http://0.0.0.0:3000/?q={searchTerms}"
+        );
+        assert_eq!(
+            template(local, &[("x-forwarded-proto", "https, http")]).await,
+            "http://0.0.0.0:3000/?q={searchTerms}"
+        );
+        assert_eq!(
+            template(
+                local,
+                &[
+                    ("forwarded", "for=192.0.2.1;proto=http"),
+                    ("x-forwarded-proto", "https")
+                ]
+            )
+            .await,
+            "http://0.0.0.0:3000/?q={searchTerms}"
+        );
+        // Anyone can send the headers, so they are ignored from untrusted peers.
+        assert_eq!(
+            template(
+                remote,
+                &[
+                    ("x-forwarded-proto", "https"),
+                    ("x-forwarded-host", "evil.example")
+                ]
+            )
+            .await,
+            "http://0.0.0.0:3000/?q={searchTerms}"
+        );
+        assert_eq!(
+            template(remote, &[("host", "evil.example\"/><x")]).await,
+            "http://0.0.0.0:3000/?q={searchTerms}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_suggestions_complete_bangs() {
+        for trigger in ["suggesttest", "suggesttestlong", "Suggesttestmixed"] {
+            BANG_CACHE.write().insert(
+                trigger.to_lowercase(),
+                Bang::new(trigger, "https://example.com/?q={{{s}}}"),
+            );
+        }
+        reindex_triggers();
+        let app_state = AppState::new(AppConfig {
+            // Unreachable, so only queries without a bang in progress reach upstream and fail.
+            search_suggestions: "http://127.0.0.1:9/suggest?q={}".to_string(),
+            ..AppConfig::default()
+        });
+        let suggest = |query: &str| {
+            suggestions_proxy(
+                RawQuery(Some(format!("q={}", urlencoding::encode(query)))),
+                State(app_state.clone()),
+            )
+        };
+
+        let response = suggest("rust !SuggestTest").await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            serde_json::json!([
+                "rust !SuggestTest",
+                [
+                    "rust !suggesttest",
+                    "rust !suggesttestlong",
+                    "rust !Suggesttestmixed"
+                ]
+            ])
+        );
+
+        for query in ["rust", "!suggesttest rust", "rust !suggesttest "] {
+            let response = suggest(query).await.into_response();
+            assert_eq!(
+                response.status(),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "{query}"
+            );
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Per-bang usage counters.
+
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How often a bang was used.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BangHits {
+    pub trigger: String,
+    pub count: u64,
+}
+
+/// Counts how often each bang trigger is used.
+///
+/// Counts live in memory only and reset on restart; [`BangStats::snapshot`] is the single
+/// read path, so persisting them later only has to serialize its output.
+#[derive(Debug, Default)]
+pub struct BangStats {
+    hits: RwLock<HashMap<String, AtomicU64>>,
+}
+
+impl BangStats {
+    /// Count one use of `trigger`.
+    pub fn record(&self, trigger: &str) {
+        // Only the first use of a trigger needs the write lock.
+        if let Some(count) = self.hits.read().get(trigger) {
+            count.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.hits
+            .write()
+            .entry(trigger.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// All counts, most used first and ties ordered by trigger.
+    #[must_use]
+    pub fn snapshot(&self) -> Vec<BangHits> {
+        let mut hits: Vec<BangHits> = self
+            .hits
+            .read()
+            .iter()
+            .map(|(trigger, count)| BangHits {
+                trigger: trigger.clone(),
+                count: count.load(Ordering::Relaxed),
+            })
+            .collect();
+        hits.sort_unstable_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.trigger.cmp(&b.trigger))
+        });
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bang_stats() {
+        let stats = BangStats::default();
+        stats.record("w");
+        stats.record("g");
+        stats.record("gh");
+        stats.record("g");
+
+        let snapshot: Vec<(String, u64)> = stats
+            .snapshot()
+            .into_iter()
+            .map(|hits| (hits.trigger, hits.count))
+            .collect();
+        assert_eq!(
+            snapshot,
+            [
+                ("g".to_string(), 2),
+                ("gh".to_string(), 1),
+                ("w".to_string(), 1)
+            ]
+        );
+    }
+}
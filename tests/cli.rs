@@ -0,0 +1,37 @@
+//! The exit status of the `redirector` binary.
+
+use std::process::Command;
+
+#[test]
+fn test_offline_resolve_without_cache_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_redirector"))
+        .args(["resolve", "--offline", "!g rust"])
+        .env("HOME", dir.path())
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("REDIRECTOR_CACHE_DIR", dir.path())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_serve_unix_socket_failure_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("redirector.sock");
+    std::fs::write(&path, "data").unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_redirector"))
+        .arg("serve")
+        .arg("--unix-socket")
+        .arg(&path)
+        .env("HOME", dir.path())
+        .env("XDG_CONFIG_HOME", dir.path())
+        .env("REDIRECTOR_CACHE_DIR", dir.path())
+        .env("REDIRECTOR_BANGS_URL", "http://127.0.0.1:9/bang.js")
+        .status()
+        .unwrap();
+    assert!(!status.success());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+}
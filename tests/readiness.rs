@@ -0,0 +1,47 @@
+//! Requests served before any bangs are loaded. This runs in its own process, so the bang
+//! cache starts out empty unlike in the other tests.
+
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{StatusCode, header};
+use axum::response::Response;
+use redirector::config::{AppConfig, AppState};
+use redirector::server::build_router;
+use redirector::{is_ready, update_cache};
+use tower::ServiceExt;
+
+async fn get(uri: &str) -> Response {
+    build_router(AppState::new(AppConfig::default()))
+        .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_ready_with_builtin_bangs() {
+    assert!(!is_ready());
+    let response = get("/readyz").await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    for uri in ["/?q=!g+rust", "/s/rust"] {
+        let response = get(uri).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE, "{uri}");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(
+            body,
+            "Bang commands are still loading, please retry shortly."
+        );
+    }
+
+    // Without a bang list, the built-in bangs are enough to serve searches.
+    update_cache(Vec::new(), &AppConfig::default());
+    assert!(is_ready());
+    assert_eq!(get("/readyz").await.status(), StatusCode::OK);
+    let response = get("/?q=!g+rust").await;
+    assert!(response.status().is_redirection());
+    assert_eq!(
+        response.headers()[header::LOCATION],
+        "https://www.google.com/search?q=rust"
+    );
+}
@@ -0,0 +1,217 @@
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{StatusCode, header};
+use axum::response::Response;
+use redirector::bang::Bang;
+use redirector::config::{AppConfig, AppState, Config, FileConfig};
+use redirector::server::build_router;
+use redirector::{BANG_CACHE, update_cache};
+use std::sync::LazyLock;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+/// Load a bang list and create an empty configuration file in a temporary directory, once for
+/// all tests, and return an app state using that file.
+fn setup() -> AppState {
+    static CONFIG_DIR: LazyLock<TempDir> = LazyLock::new(|| {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("config.toml"), "").unwrap();
+        update_cache(
+            vec![Bang::new("routestest", "https://example.com/?q={{{s}}}")],
+            &AppConfig::default(),
+        );
+        dir
+    });
+    AppState::new(AppConfig {
+        bangs: Some(Vec::new()),
+        ..AppConfig::default()
+    })
+    .with_config_path(CONFIG_DIR.path().join("config.toml"))
+}
+
+async fn send(request: Request) -> Response {
+    build_router(setup()).oneshot(request).await.unwrap()
+}
+
+async fn get(uri: &str) -> Response {
+    send(Request::get(uri).body(Body::empty()).unwrap()).await
+}
+
+#[tokio::test]
+async fn test_bang_redirects() {
+    let response = get("/?q=!g+rust").await;
+    assert!(response.status().is_redirection());
+    assert_eq!(
+        response.headers()[header::LOCATION],
+        "https://www.google.com/search?q=rust"
+    );
+}
+
+#[tokio::test]
+async fn test_path_search() {
+    for (uri, expected) in [
+        (
+            "/s/!g+rust+programming",
+            "https://www.google.com/search?q=rust%20programming",
+        ),
+        (
+            "/s/!g%20c%2B%2B%2Fstd",
+            "https://www.google.com/search?q=c%2B%2B/std",
+        ),
+        ("/s/!g+a/b", "https://www.google.com/search?q=a/b"),
+        ("/s/", "/bangs"),
+    ] {
+        let response = get(uri).await;
+        assert!(response.status().is_redirection(), "{uri}");
+        assert_eq!(response.headers()[header::LOCATION], expected, "{uri}");
+    }
+}
+
+#[tokio::test]
+async fn test_no_query_redirects_to_bangs() {
+    let response = get("/").await;
+    assert!(response.status().is_redirection());
+    assert_eq!(response.headers()[header::LOCATION], "/bangs");
+}
+
+#[tokio::test]
+async fn test_opensearch_content_type() {
+    let response = get("/opensearch.xml").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/opensearchdescription+xml"
+    );
+}
+
+#[tokio::test]
+async fn test_cache_control() {
+    for (uri, cache_control) in [
+        ("/?q=!g+rust", "no-store"),
+        ("/", "no-store"),
+        ("/opensearch.xml", "public, max-age=86400"),
+        ("/favicon.ico", "public, max-age=604800"),
+        ("/manifest.json", "public, max-age=604800"),
+    ] {
+        let response = get(uri).await;
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            cache_control,
+            "{uri}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_security_headers() {
+    let response = get("/bangs").await;
+    let headers = response.headers();
+    assert!(
+        headers[header::CONTENT_SECURITY_POLICY]
+            .to_str()
+            .unwrap()
+            .contains("frame-ancestors 'none'")
+    );
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    assert_eq!(headers[header::REFERRER_POLICY], "no-referrer");
+    assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+
+    let response = get("/?q=!g+rust").await;
+    assert!(response.headers().get(header::REFERRER_POLICY).is_none());
+
+    let config: FileConfig = toml::from_str(
+        r#"security_headers = { "X-Frame-Options" = "SAMEORIGIN", "Content-Security-Policy" = "" }"#,
+    )
+    .unwrap();
+    let config = FileConfig {
+        bangs: Some(Vec::new()),
+        ..config
+    };
+    setup();
+    let response = build_router(AppState::new(config.merge(Config::default())))
+        .oneshot(Request::get("/bangs").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let headers = response.headers();
+    assert_eq!(headers[header::X_FRAME_OPTIONS], "SAMEORIGIN");
+    assert!(headers.get(header::CONTENT_SECURITY_POLICY).is_none());
+    assert_eq!(headers[header::REFERRER_POLICY], "no-referrer");
+}
+
+#[tokio::test]
+async fn test_add_bang_inserts_into_cache() {
+    let response = send(
+        Request::post("/add_bang")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                r#"{"trigger": "routetest", "url_template": "https://example.com/?q={{{s}}}"}"#,
+            ))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(BANG_CACHE.read().contains_key("routetest"));
+
+    let response = get("/?q=!routetest+rust").await;
+    assert_eq!(
+        response.headers()[header::LOCATION],
+        "https://example.com/?q=rust"
+    );
+}
+
+#[tokio::test]
+async fn test_merge_into_larger_app() {
+    let app = axum::Router::new()
+        .route("/other", axum::routing::get(|| async { "other" }))
+        .merge(build_router(setup()));
+
+    let response = app
+        .clone()
+        .oneshot(Request::get("/other").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app
+        .oneshot(Request::get("/healthz").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_base_path() {
+    let file = FileConfig {
+        base_path: Some(" search/ ".to_string()),
+        bangs: Some(Vec::new()),
+        ..FileConfig::default()
+    };
+    setup();
+    let config = file.merge(Config::default());
+    assert_eq!(config.base_path, "/search");
+    let app = build_router(AppState::new(config));
+    let get = |uri: &str| {
+        app.clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+    };
+
+    for uri in ["/search?q=!g+rust", "/search/?q=!g+rust"] {
+        let response = get(uri).await.unwrap();
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://www.google.com/search?q=rust"
+        );
+    }
+    let response = get("/search").await.unwrap();
+    assert_eq!(response.headers()[header::LOCATION], "/search/bangs");
+    let response = get("/search/bangs").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = get("/search/opensearch.xml").await.unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body.contains(r#"template="http://0.0.0.0:3000/search/?q={searchTerms}""#));
+    assert!(body.contains(r#"template="http://0.0.0.0:3000/search/suggest?q={searchTerms}""#));
+    let response = get("/bangs").await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}